@@ -1,4 +1,17 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+//! Benchmarks for stripping comments.
+//!
+//! Run with `cargo bench --bench strip`. Besides the real-world `tsconfig` input, the `scenarios`
+//! group runs each stripping API over generated comment-heavy and comment-light documents, a
+//! comment-free document of about 1MB, one made of long string values, and one full of trailing
+//! commas, so throughput can be compared across APIs and tracked over time. The `scan_strategy`
+//! group compares each [`ScanStrategy`] on the same documents, the `per_byte` group feeds every
+//! byte outside comments through the state machine one at a time, and the `reader_buffer` group
+//! runs the reader with buffers of several sizes.
+
+use std::io::Read;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use json_strip_comments::{strip_comments_in_place, CommentSettings, ScanStrategy, StripComments};
 
 pub fn bench(c: &mut Criterion) {
     c.bench_function("tsconfig", |b| {
@@ -9,7 +22,166 @@ pub fn bench(c: &mut Criterion) {
     });
 }
 
-criterion_group!(strip, bench);
+pub fn scenarios(c: &mut Criterion) {
+    let inputs = [
+        ("comment_heavy", generate(1000, true)),
+        ("comment_light", generate(1000, false)),
+        ("comment_free_1mb", generate(20_000, false)),
+        ("long_strings", generate_long_strings(100)),
+        ("trailing_commas", generate_trailing_commas(1000)),
+    ];
+
+    let mut group = c.benchmark_group("scenarios");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("in_place", name), input, |b, input| {
+            b.iter(|| {
+                let mut data = input.clone();
+                strip_comments_in_place(&mut data, CommentSettings::all()).unwrap();
+            });
+        });
+        group.bench_with_input(BenchmarkId::new("reader", name), input, |b, input| {
+            b.iter(|| {
+                let mut data = String::with_capacity(input.len());
+                StripComments::new(input.as_bytes())
+                    .read_to_string(&mut data)
+                    .unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+pub fn scan_strategies(c: &mut Criterion) {
+    let inputs = [
+        ("tsconfig", TSCONFIG.to_string()),
+        ("comment_heavy", generate(1000, true)),
+        ("comment_light", generate(1000, false)),
+        (
+            "minified",
+            generate(1000, false).split_whitespace().collect(),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("scan_strategy");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        for scan_strategy in [ScanStrategy::Branch, ScanStrategy::Table] {
+            let mut settings = CommentSettings::all();
+            settings.scan_strategy = scan_strategy;
+            let id = BenchmarkId::new(format!("{scan_strategy:?}"), name);
+            group.bench_with_input(id, input, |b, input| {
+                b.iter(|| {
+                    let mut data = input.clone();
+                    strip_comments_in_place(&mut data, settings).unwrap();
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+/// With [`ScanStrategy::Branch`] and `forbid_control_chars_in_strings`, neither runs of plain
+/// text nor string contents are skipped in bulk, so this measures the per-byte cost of the state
+/// machine itself.
+pub fn per_byte(c: &mut Criterion) {
+    let mut settings = CommentSettings::all();
+    settings.scan_strategy = ScanStrategy::Branch;
+    settings.forbid_control_chars_in_strings = true;
+    let inputs = [
+        ("tsconfig", TSCONFIG.to_string()),
+        ("comment_light", generate(1000, false)),
+        ("long_strings", generate_long_strings(100)),
+    ];
+
+    let mut group = c.benchmark_group("per_byte");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("in_place", name), input, |b, input| {
+            b.iter(|| {
+                let mut data = input.clone();
+                strip_comments_in_place(&mut data, settings).unwrap();
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Generates an object with `entries` members, optionally interleaved with every kind of comment
+/// and with trailing commas.
+fn generate(entries: usize, comments: bool) -> String {
+    let mut s = String::from("{\n");
+    for i in 0..entries {
+        if comments {
+            s.push_str("  // a line comment describing the next entry\n");
+            s.push_str("  /* a block comment\n     spanning lines */\n");
+        }
+        s.push_str(&format!("  \"key{i}\": [\"value {i}\", {i}, true, null"));
+        if comments {
+            s.push_str(", # hash comment");
+        }
+        s.push_str("\n  ],\n");
+    }
+    s.push_str("  \"last\": {}\n}\n");
+    s
+}
+
+/// Generates an array of `entries` small arrays and objects, each ending in a trailing comma
+/// followed by whitespace and a comment, so that every one is looked ahead over.
+fn generate_trailing_commas(entries: usize) -> String {
+    let mut s = String::from("[\n");
+    for i in 0..entries {
+        s.push_str(&format!(
+            "  [{i}, \"{i}\", [true, null,], ],\n  {{\"key{i}\": {{\"a\": {i},}}, /* last */\n  }},\n"
+        ));
+    }
+    s.push_str("]\n");
+    s
+}
+
+/// Generates an object with `entries` members whose values are long strings, with escapes but
+/// no comments.
+fn generate_long_strings(entries: usize) -> String {
+    let value = "lorem ipsum dolor sit amet, \\\"consectetur\\\" adipiscing elit ".repeat(40);
+    let mut s = String::from("{\n");
+    for i in 0..entries {
+        s.push_str(&format!("  \"key{i}\": \"{value}\",\n"));
+    }
+    s.push_str("  \"last\": \"\"\n}\n");
+    s
+}
+
+pub fn reader_buffers(c: &mut Criterion) {
+    let inputs = [
+        ("comment_heavy", generate(1000, true)),
+        ("trailing_commas", generate_trailing_commas(1000)),
+    ];
+
+    let mut group = c.benchmark_group("reader_buffer");
+    for (name, input) in &inputs {
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        for size in [64, 1024, 8192, 65536] {
+            let id = BenchmarkId::new(format!("{size}"), name);
+            group.bench_with_input(id, input, |b, input| {
+                let mut buf = vec![0; size];
+                b.iter(|| {
+                    let mut reader = StripComments::new(input.as_bytes());
+                    while reader.read(&mut buf).unwrap() > 0 {}
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    strip,
+    bench,
+    scenarios,
+    scan_strategies,
+    per_byte,
+    reader_buffers
+);
 criterion_main!(strip);
 
 const TSCONFIG: &str = r#"
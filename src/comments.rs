@@ -0,0 +1,1525 @@
+//! APIs that work with the comments themselves rather than blanking them.
+
+use std::{
+    borrow::Cow,
+    io::{Error, ErrorKind, Result},
+    ops::Range,
+};
+
+use crate::{
+    strip_buf_observed, strip_str_observed, transition, CommentKind, CommentPrefix,
+    CommentSettings, Observer,
+    State::{
+        self, InBlockComment, InComment, InLineComment, InString, LineCommentContinuation,
+        MaybeCommentEnd, MaybeLineSeparator, Top,
+    },
+    StripError,
+};
+
+/// What stripping `s` removes.
+#[derive(Default)]
+pub(crate) struct Removals {
+    /// Byte ranges of the comments, in order.
+    pub(crate) comments: Vec<Range<usize>>,
+    /// Byte offsets of the trailing commas, in order.
+    pub(crate) commas: Vec<usize>,
+    /// Where the last string or comment began.
+    start: usize,
+}
+
+impl Observer for Removals {
+    fn trailing_comma(&mut self, pos: usize) {
+        self.commas.push(pos);
+    }
+
+    fn comment_start(&mut self, pos: usize) {
+        self.start = pos;
+    }
+
+    fn comment_end(&mut self, pos: usize) {
+        self.comments.push(self.start..pos);
+    }
+
+    fn string_start(&mut self, pos: usize) {
+        self.start = pos;
+    }
+}
+
+impl Removals {
+    /// Merges comments and trailing commas into one list of ranges, in order.
+    pub(crate) fn ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<_> = self.comments.clone();
+        ranges.extend(self.commas.iter().map(|&comma| comma..comma + 1));
+        ranges.sort_unstable_by_key(|range| range.start);
+        ranges
+    }
+
+    /// Ends a line comment left open at the end of input of length `len`, and errors if the
+    /// input ended inside a string or block comment.
+    fn finish(&mut self, state: State, len: usize) -> Result<()> {
+        match state {
+            Top => {}
+            InLineComment | LineCommentContinuation | MaybeLineSeparator(_) => {
+                self.comments.push(self.start..len);
+            }
+            _ => return Err(StripError::at_eof(state, self.start, len).into()),
+        }
+        Ok(())
+    }
+}
+
+/// Finds everything stripping `s` would remove, without modifying it.
+///
+/// Unlike [`crate::strip_comments_in_place`], an unterminated string or block comment is an
+/// error.
+pub(crate) fn removals(s: &str, settings: CommentSettings) -> Result<Removals> {
+    let mut removals = Removals::default();
+    let mut state: State = Top;
+    let mut buf = s.as_bytes().to_vec();
+    strip_buf_observed(&mut state, &mut buf, settings, &mut removals)?;
+    removals.finish(state, s.len())?;
+    Ok(removals)
+}
+
+/// Returns every edit [`crate::strip`] would make to `s`, without modifying it.
+///
+/// Unlike [`crate::strip`], an unterminated string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{plan, Edit};
+///
+/// let input = "[1, /* two */ 2,]";
+/// let plan = plan(input).unwrap();
+/// assert_eq!(plan.edits, [Edit::Comment(4..13), Edit::TrailingComma(15)]);
+///
+/// // Apply only the comment removal.
+/// let mut s = String::from(input);
+/// let comments_only = json_strip_comments::StripPlan {
+///     edits: plan.edits.into_iter().filter(|edit| matches!(edit, Edit::Comment(_))).collect(),
+/// };
+/// comments_only.apply(&mut s).unwrap();
+/// assert_eq!(s, "[1,           2,]");
+/// ```
+pub fn plan(s: &str) -> Result<StripPlan> {
+    let removals = removals(s, CommentSettings::all())?;
+    let mut edits: Vec<_> = removals.comments.into_iter().map(Edit::Comment).collect();
+    edits.extend(removals.commas.into_iter().map(Edit::TrailingComma));
+    edits.sort_unstable_by_key(|edit| edit.range().start);
+    Ok(StripPlan { edits })
+}
+
+/// The edits stripping a string makes, returned by [`plan`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StripPlan {
+    /// The edits, in order of position.
+    pub edits: Vec<Edit>,
+}
+
+/// One edit in a [`StripPlan`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Edit {
+    /// A comment to blank, as a byte range.
+    Comment(Range<usize>),
+    /// A trailing comma to blank, as a byte offset.
+    TrailingComma(usize),
+}
+
+impl Edit {
+    /// The bytes this edit blanks.
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            Self::Comment(range) => range.clone(),
+            &Self::TrailingComma(comma) => comma..comma + 1,
+        }
+    }
+}
+
+impl StripPlan {
+    /// Blanks the bytes of every edit in `s`.
+    ///
+    /// Applying the full plan for `s` gives the same result as [`crate::strip`]. Edits may be
+    /// removed first to apply only some of them. Errors with [`ErrorKind::InvalidInput`],
+    /// without changing `s`, if an edit is out of bounds or would split a character.
+    pub fn apply(&self, s: &mut str) -> Result<()> {
+        for edit in &self.edits {
+            let range = edit.range();
+            if range.end > s.len()
+                || !s.is_char_boundary(range.start)
+                || !s.is_char_boundary(range.end)
+            {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("edit {range:?} does not fit the string"),
+                ));
+            }
+        }
+        // Safety: every range covers whole characters, and spaces are valid UTF-8.
+        let buf = unsafe { s.as_bytes_mut() };
+        for edit in &self.edits {
+            buf[edit.range()].fill(b' ');
+        }
+        Ok(())
+    }
+}
+
+/// A replacement of a byte range with new text, as sent to editors in an LSP `TextEdit`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TextEdit {
+    /// The bytes of the original text to replace. Converting them to lines and columns is left
+    /// to the caller, since editors differ in how they count columns.
+    pub range: Range<usize>,
+    /// The text that replaces them.
+    pub new_text: String,
+}
+
+/// Returns the edits that turn `s` into the output of [`crate::strip`], one per comment and
+/// trailing comma, in order.
+///
+/// Each edit replaces its bytes with as many spaces, so every edit's range is valid in the
+/// original text and the edits can be sent to an editor as a batch. Like [`plan`], an
+/// unterminated string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{text_edits, TextEdit};
+///
+/// let edits = text_edits("[1, /* two */ 2,]").unwrap();
+/// assert_eq!(
+///     edits,
+///     [
+///         TextEdit { range: 4..13, new_text: " ".repeat(9) },
+///         TextEdit { range: 15..16, new_text: " ".into() },
+///     ]
+/// );
+/// ```
+pub fn text_edits(s: &str) -> Result<Vec<TextEdit>> {
+    Ok(plan(s)?
+        .edits
+        .iter()
+        .map(|edit| {
+            let range = edit.range();
+            let new_text = " ".repeat(range.len());
+            TextEdit { range, new_text }
+        })
+        .collect())
+}
+
+/// Iterates over the comments in `s` without allocating, yielding each comment's kind and byte
+/// range.
+///
+/// The state machine only runs as far as needed for each call to `next`. An invalid comment
+/// start, or an unterminated string or block comment, is yielded as an error, after which the
+/// iterator ends.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{comments, CommentKind};
+///
+/// let input = "{\"a\": 1 /* one */, # two\n}";
+/// let found: Vec<_> = comments(input)
+///     .map(|comment| comment.map(|(kind, range)| (kind, &input[range])))
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+///
+/// assert_eq!(found, [(CommentKind::Block, "/* one */"), (CommentKind::Hash, "# two")]);
+/// ```
+pub fn comments(s: &str) -> Comments<'_> {
+    Comments {
+        bytes: s.as_bytes(),
+        pos: 0,
+        state: Top,
+        start: 0,
+        kind: CommentKind::Block,
+    }
+}
+
+/// Strips `s` in place like [`crate::strip`], first passing each comment to `sink` with its
+/// kind, byte range, and text, for tools that extract documentation from comments.
+///
+/// As with [`comments`], an invalid comment start, or an unterminated string or block comment,
+/// is an error. Then `s` is left unchanged, though `sink` has seen the comments before it.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_with_comments, CommentKind};
+///
+/// let mut s = String::from("{\n  /** The name. */\n  \"name\": \"x\" // unused\n}");
+/// let mut docs = Vec::new();
+/// strip_with_comments(&mut s, |kind, _, text| {
+///     if kind == CommentKind::Block && text.starts_with("/**") {
+///         docs.push(text.to_owned());
+///     }
+/// })
+/// .unwrap();
+///
+/// assert_eq!(docs, ["/** The name. */"]);
+/// assert_eq!(s, "{\n                  \n  \"name\": \"x\"          \n}");
+/// ```
+pub fn strip_with_comments(
+    s: &mut str,
+    mut sink: impl FnMut(CommentKind, Range<usize>, &str),
+) -> Result<()> {
+    for comment in comments(s) {
+        let (kind, range) = comment?;
+        sink(kind, range.clone(), &s[range]);
+    }
+    crate::strip(s)
+}
+
+/// Iterator over the comments in a string, returned by [`comments`].
+#[derive(Clone, Debug)]
+pub struct Comments<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    state: State,
+    start: usize,
+    kind: CommentKind,
+}
+
+impl Iterator for Comments<'_> {
+    type Item = Result<(CommentKind, Range<usize>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&c) = self.bytes.get(self.pos) {
+            let i = self.pos;
+            self.pos += 1;
+            let state = self.state;
+            self.state = match transition(state, c, CommentSettings::all()) {
+                Ok((state, _)) => state,
+                Err(kind) => {
+                    self.pos = self.bytes.len();
+                    self.state = Top;
+                    return Some(Err(StripError::new(kind, i).into()));
+                }
+            };
+            match (state, self.state) {
+                (Top, InComment) => self.start = i,
+                (Top, InLineComment) => {
+                    self.start = i;
+                    self.kind = CommentKind::Hash;
+                }
+                (InComment, InBlockComment) => self.kind = CommentKind::Block,
+                (InComment, InLineComment) => self.kind = CommentKind::Slash,
+                (Top, InString) => self.start = i,
+                (MaybeCommentEnd, Top) => return Some(Ok((self.kind, self.start..i + 1))),
+                (InLineComment, Top) => return Some(Ok((self.kind, self.start..i))),
+                _ => {}
+            }
+        }
+        match std::mem::replace(&mut self.state, Top) {
+            Top => None,
+            InLineComment => Some(Ok((self.kind, self.start..self.bytes.len()))),
+            state => Some(Err(
+                StripError::at_eof(state, self.start, self.bytes.len()).into()
+            )),
+        }
+    }
+}
+
+/// Strips `s` like [`crate::strip`], also returning anchors for mapping offsets in the output
+/// back to `s`.
+///
+/// Each anchor is an `(output_offset, input_offset)` pair, placed at the start and end of
+/// every removed comment and trailing comma, in order. Since stripping only blanks, the two
+/// offsets are always equal, but the anchors still show where comments were. Unlike
+/// [`crate::strip`], an unterminated string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_with_sourcemap;
+///
+/// let (out, anchors) = strip_with_sourcemap("[1, /* two */ 2,]").unwrap();
+/// assert_eq!(out, "[1,           2 ]");
+/// assert_eq!(anchors, [(4, 4), (13, 13), (15, 15), (16, 16)]);
+/// ```
+pub fn strip_with_sourcemap(s: &str) -> Result<(String, Vec<(usize, usize)>)> {
+    let mut out = s.to_owned();
+    let mut removals = Removals::default();
+    let state = strip_str_observed(&mut out, CommentSettings::all(), &mut removals)?;
+    removals.finish(state, s.len())?;
+    let mut anchors: Vec<(usize, usize)> = Vec::new();
+    for range in removals.ranges() {
+        for pos in [range.start, range.end] {
+            // Adjacent removals share an anchor.
+            if anchors.last() != Some(&(pos, pos)) {
+                anchors.push((pos, pos));
+            }
+        }
+    }
+    Ok((out, anchors))
+}
+
+/// Returns the byte ranges of all comments in `s`, in order.
+pub(crate) fn comment_ranges(s: &str, settings: CommentSettings) -> Result<Vec<Range<usize>>> {
+    removals(s, settings).map(|removals| removals.comments)
+}
+
+/// Removes comments and trailing commas instead of blanking them.
+///
+/// Each comment is replaced by only the line breaks it contained, so line numbers stay the same
+/// but no runs of spaces are left behind. Spaces and tabs that would end up at the end of a
+/// line are removed as well. A comment between two tokens on one line, as in `1/**/2`, becomes
+/// a single space so the tokens stay apart. Unlike the in-place functions, byte offsets change.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_compact;
+///
+/// let input = "{\n  \"a\": [1, 2,], /* multi\n  line */\n  \"b\": 3 // end\n}";
+/// assert_eq!(strip_compact(input).unwrap(), "{\n  \"a\": [1, 2],\n\n  \"b\": 3\n}");
+/// ```
+pub fn strip_compact(s: &str) -> Result<String> {
+    strip_compact_with_options(s, CompactOptions::default())
+}
+
+/// Line break styles for [`CompactOptions::line_ending`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Options for [`strip_compact_with_options`].
+///
+/// The default gives the same output as [`strip_compact`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompactOptions {
+    /// If set, the line breaks kept from removed comments are written in this style, whatever
+    /// style they had.
+    ///
+    /// `\r\n`, `\n`, and a lone `\r` each count as one line break. Line breaks outside
+    /// comments, including inside strings, are left alone.
+    pub line_ending: Option<LineEnding>,
+    /// True if a trailing comma in an array is kept and followed by `null`, so `[1, 2,]`
+    /// becomes `[1, 2,null]` and keeps its number of elements for tools that count commas.
+    /// Trailing commas in objects are still removed.
+    pub trailing_comma_to_null: bool,
+}
+
+/// Like [`strip_compact`], but the line breaks kept from removed comments are written as
+/// `line_ending`. See [`CompactOptions::line_ending`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_compact_with_line_ending, LineEnding};
+///
+/// let input = "{\r\n  /* one\r\n  two */\r\n  \"a\": 1\r\n}";
+/// assert_eq!(
+///     strip_compact_with_line_ending(input, LineEnding::Lf).unwrap(),
+///     "{\r\n\n\r\n  \"a\": 1\r\n}"
+/// );
+/// ```
+pub fn strip_compact_with_line_ending(s: &str, line_ending: LineEnding) -> Result<String> {
+    strip_compact_with_options(
+        s,
+        CompactOptions {
+            line_ending: Some(line_ending),
+            ..CompactOptions::default()
+        },
+    )
+}
+
+/// Like [`strip_compact`], with [`CompactOptions`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_compact_with_options, CompactOptions};
+///
+/// let options = CompactOptions {
+///     trailing_comma_to_null: true,
+///     ..CompactOptions::default()
+/// };
+/// let input = "{\"a\": [1, 2,], \"b\": {\"c\": 4,}, /* end */}";
+/// assert_eq!(
+///     strip_compact_with_options(input, options).unwrap(),
+///     "{\"a\": [1, 2,null], \"b\": {\"c\": 4} }"
+/// );
+/// ```
+pub fn strip_compact_with_options(s: &str, options: CompactOptions) -> Result<String> {
+    let removals = removals(s, CommentSettings::all())?;
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    let mut commas = removals.commas.iter().peekable();
+    let remove_comma = |out: &mut String, last: &mut usize, comma: usize| {
+        if options.trailing_comma_to_null && closer_after(s, comma, &removals.comments) == b']' {
+            out.push_str(&s[*last..=comma]);
+            out.push_str("null");
+        } else {
+            out.push_str(&s[*last..comma]);
+        }
+        *last = comma + 1;
+    };
+    let joins =
+        |c: Option<&u8>| c.is_some_and(|&c| c.is_ascii_alphanumeric() || b".-+".contains(&c));
+    for range in &removals.comments {
+        // Trailing commas are never inside comments, so they can be merged in between them.
+        while let Some(&comma) = commas.next_if(|&&comma| comma < range.start) {
+            remove_comma(&mut out, &mut last, comma);
+        }
+        out.push_str(&s[last..range.start]);
+        let comment = &s[range.clone()];
+        let ends_line = matches!(s.as_bytes().get(range.end), None | Some(b'\r' | b'\n'));
+        if ends_line || comment.contains('\n') {
+            out.truncate(out.trim_end_matches([' ', '\t']).len());
+        }
+        match options.line_ending {
+            None => out.extend(comment.chars().filter(|&c| c == '\n' || c == '\r')),
+            Some(line_ending) => {
+                // A line comment ends before the `\n` of a `\r\n`, which is not ours to change.
+                let (comment, cr) = match comment.strip_suffix('\r') {
+                    Some(rest) if s.as_bytes().get(range.end) == Some(&b'\n') => (rest, "\r"),
+                    _ => (comment, ""),
+                };
+                let breaks = comment.matches('\n').count() + comment.matches('\r').count()
+                    - comment.matches("\r\n").count();
+                out.extend(std::iter::repeat_n(line_ending.as_str(), breaks));
+                out.push_str(cr);
+            }
+        }
+        // Removing the comment outright would join the tokens around it into one.
+        if joins(out.as_bytes().last()) && joins(s.as_bytes().get(range.end)) {
+            out.push(' ');
+        }
+        last = range.end;
+    }
+    for &comma in commas {
+        remove_comma(&mut out, &mut last, comma);
+    }
+    out.push_str(&s[last..]);
+    Ok(out)
+}
+
+/// Returns the `]` or `}` that makes the comma at `comma` a trailing comma.
+fn closer_after(s: &str, comma: usize, comments: &[Range<usize>]) -> u8 {
+    let bytes = s.as_bytes();
+    let mut i = comma + 1;
+    let mut comments = comments[comments.partition_point(|range| range.start < i)..].iter();
+    loop {
+        while bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        match comments.next() {
+            Some(range) if range.start == i => i = range.end,
+            _ => return bytes[i],
+        }
+    }
+}
+
+/// Strips `s` in place like [`crate::strip`], except that comments whose text matches
+/// `pattern` are left verbatim.
+///
+/// The text of a comment is what follows `//` or `#`, or what is between `/*` and `*/`, so
+/// `^\s*@ts-` matches `// @ts-ignore`. This is useful for keeping tooling directives such as
+/// `// eslint-disable-next-line` in output that linters still read.
+///
+/// Enabled with the `regex` feature. Unlike [`crate::strip_comments_in_place`], an unterminated
+/// string or block comment is an error.
+///
+/// The output is not plain JSON while it contains preserved comments, so stripping it again
+/// with [`crate::strip`] is not a no-op: the preserved comments are removed then. Stripping it
+/// again with the same pattern does not change it.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_preserving_comments_matching;
+/// use regex::Regex;
+///
+/// let mut s = String::from("{\n  // @ts-ignore\n  \"a\": 1, // other\n}");
+/// strip_preserving_comments_matching(&mut s, &Regex::new(r"^\s*@ts-").unwrap()).unwrap();
+///
+/// assert_eq!(s, "{\n  // @ts-ignore\n  \"a\": 1          \n}");
+/// ```
+#[cfg(feature = "regex")]
+pub fn strip_preserving_comments_matching(s: &mut str, pattern: &regex::Regex) -> Result<()> {
+    let removals = removals(s, CommentSettings::all())?;
+    let mut blank: Vec<_> = removals
+        .comments
+        .into_iter()
+        .filter(|range| !pattern.is_match(comment_text(&s[range.clone()])))
+        .collect();
+    blank.extend(removals.commas.iter().map(|&comma| comma..comma + 1));
+    // Safety: only whole comments and commas are replaced, which begin and end at ASCII bytes.
+    let buf = unsafe { s.as_bytes_mut() };
+    for range in blank {
+        buf[range].fill(b' ');
+    }
+    Ok(())
+}
+
+/// Returns `comment` without its delimiters.
+#[cfg(feature = "regex")]
+fn comment_text(comment: &str) -> &str {
+    if let Some(text) = comment.strip_prefix("/*") {
+        text.strip_suffix("*/").unwrap_or(text)
+    } else {
+        comment
+            .strip_prefix("//")
+            .or_else(|| comment.strip_prefix('#'))
+            .unwrap_or(comment)
+    }
+}
+
+/// Strips `s` in place like [`crate::strip`], except that block comments starting with `/*`
+/// followed by `prefix` are left verbatim, such as `/*! ... */` license banners with a prefix of
+/// `!`.
+///
+/// This is [`CommentSettings::preserve_comment_prefix`] with the other settings at their
+/// defaults, which [`StripComments`](crate::StripComments) and the other APIs taking settings
+/// honour too. Line comments are always removed. Like [`strip_preserving_comments_matching`], an
+/// unterminated string or block comment is an error, in which case `s` is left unchanged, and
+/// the output is not plain JSON while it contains preserved comments. A prefix longer than
+/// [`CommentPrefix::MAX_LEN`] bytes, or containing `*/`, is an [`ErrorKind::InvalidInput`]
+/// error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_preserving_comments_with_prefix;
+///
+/// let mut s = String::from("/*! (c) Example */\n/* build 12 */ {\"a\": 1} //! done");
+/// strip_preserving_comments_with_prefix(&mut s, "!").unwrap();
+///
+/// assert_eq!(s, "/*! (c) Example */\n               {\"a\": 1}         ");
+/// ```
+pub fn strip_preserving_comments_with_prefix(s: &mut str, prefix: &str) -> Result<()> {
+    let prefix = CommentPrefix::new(prefix.as_bytes()).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            "comment prefix is too long or contains `*/`",
+        )
+    })?;
+    let settings = CommentSettings {
+        preserve_comment_prefix: Some(prefix),
+        ..CommentSettings::all()
+    };
+    let mut stripped = s.to_owned();
+    crate::strip_comments_in_place_strict(&mut stripped, settings)?;
+    // Safety: the copy is UTF-8 and as long as `s`, since stripping only replaces bytes.
+    unsafe { s.as_bytes_mut() }.copy_from_slice(stripped.as_bytes());
+    Ok(())
+}
+
+/// Which comments [`strip_preserving_comments_in`] keeps, by the innermost bracket around them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Context {
+    /// Keep comments directly inside `{ ... }`.
+    Objects,
+    /// Keep comments directly inside `[ ... ]`.
+    Arrays,
+    /// Keep comments inside any brackets.
+    Both,
+    /// Keep no comments, like [`crate::strip`].
+    Neither,
+}
+
+/// Strips `s` in place like [`crate::strip`], except that comments whose innermost enclosing
+/// bracket is selected by `preserve_comments_in` are left verbatim.
+///
+/// A comment's context is decided where it starts, so in `{"a": [1 /* x */]}` the comment is
+/// in an array. Comments outside all brackets are always removed. Like
+/// [`strip_preserving_comments_matching`], an unterminated string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_preserving_comments_in, Context};
+///
+/// let mut s = String::from("{\n  // the port\n  \"port\": 80,\n  \"tags\": [\"a\" /* noise */],\n}");
+/// strip_preserving_comments_in(&mut s, Context::Objects).unwrap();
+///
+/// assert_eq!(s, "{\n  // the port\n  \"port\": 80,\n  \"tags\": [\"a\"            ] \n}");
+/// ```
+pub fn strip_preserving_comments_in(s: &mut str, preserve_comments_in: Context) -> Result<()> {
+    let removals = removals(s, CommentSettings::all())?;
+    let bytes = s.as_bytes();
+    let mut blank = Vec::new();
+    let mut open = Vec::new();
+    let mut comments = removals.comments.into_iter().peekable();
+    let mut i = 0;
+    while i < bytes.len() {
+        if let Some(comment) = comments.next_if(|comment| comment.start == i) {
+            let keep = match (preserve_comments_in, open.last()) {
+                (_, None) | (Context::Neither, _) => false,
+                (Context::Both, _) => true,
+                (Context::Objects, Some(&bracket)) => bracket == b'{',
+                (Context::Arrays, Some(&bracket)) => bracket == b'[',
+            };
+            i = comment.end;
+            if !keep {
+                blank.push(comment);
+            }
+            continue;
+        }
+        match bytes[i] {
+            b'{' | b'[' => open.push(bytes[i]),
+            b'}' | b']' => {
+                open.pop();
+            }
+            b'"' => {
+                // Skip to the closing quote, which `removals` has checked exists.
+                i += 1;
+                while bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    blank.extend(removals.commas.iter().map(|&comma| comma..comma + 1));
+    // Safety: only whole comments and commas are replaced, which begin and end at ASCII bytes.
+    let buf = unsafe { s.as_bytes_mut() };
+    for range in blank {
+        buf[range].fill(b' ');
+    }
+    Ok(())
+}
+
+/// Strips `s` without modifying or copying it, as a sequence of chunks that concatenate to the
+/// output of [`crate::strip`].
+///
+/// Unchanged text is borrowed from `s`. Removed comments and trailing commas become runs of
+/// spaces, which are borrowed from a static string unless they are very long. Like
+/// [`comments`], the state machine only runs as far as needed for each call to `next`. An
+/// invalid comment start, or an unterminated string or block comment, is yielded as an error
+/// after the text before it, and the iterator then ends.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_chunks;
+/// use std::borrow::Cow;
+///
+/// let chunks: Vec<_> = strip_chunks(r#"{"a": 1 /* one */}"#).collect::<Result<_, _>>().unwrap();
+/// assert_eq!(chunks, [r#"{"a": 1 "#, "         ", "}"]);
+/// assert!(chunks.iter().all(|chunk| matches!(chunk, Cow::Borrowed(_))));
+/// ```
+pub fn strip_chunks(s: &str) -> StripChunks<'_> {
+    StripChunks {
+        s,
+        pos: 0,
+        state: Top,
+        start: 0,
+        removed: None,
+        error: None,
+        last: 0,
+    }
+}
+
+/// Iterator over the chunks of stripped text, returned by [`strip_chunks`].
+#[derive(Clone, Debug)]
+pub struct StripChunks<'a> {
+    s: &'a str,
+    /// The next byte for the state machine.
+    pos: usize,
+    state: State,
+    /// Where the last string or comment began.
+    start: usize,
+    /// A removed range to blank on the next call, after the text before it was returned.
+    removed: Option<Range<usize>>,
+    /// An error to return on the next call, after the text before it was returned.
+    error: Option<StripError>,
+    /// The end of what has been returned.
+    last: usize,
+}
+
+impl StripChunks<'_> {
+    /// Runs the state machine up to the end of the next comment or trailing comma, returning
+    /// its range.
+    fn next_removal(&mut self) -> std::result::Result<Option<Range<usize>>, StripError> {
+        let settings = CommentSettings::all();
+        let bytes = self.s.as_bytes();
+        while let Some(&c) = bytes.get(self.pos) {
+            let i = self.pos;
+            self.pos += 1;
+            let state = self.state;
+            self.state = match transition(state, c, settings) {
+                Ok((state, _)) => state,
+                Err(kind) => return Err(StripError::new(kind, i)),
+            };
+            match (state, self.state) {
+                (Top, Top) if c == b',' && is_trailing(bytes, self.pos) => {
+                    return Ok(Some(i..i + 1))
+                }
+                (Top, InString | InComment | InLineComment) => self.start = i,
+                (MaybeCommentEnd, Top) => return Ok(Some(self.start..i + 1)),
+                (InLineComment, Top) => return Ok(Some(self.start..i)),
+                _ => {}
+            }
+        }
+        match std::mem::replace(&mut self.state, Top) {
+            Top => Ok(None),
+            InLineComment => Ok(Some(self.start..bytes.len())),
+            state => Err(StripError::at_eof(state, self.start, bytes.len())),
+        }
+    }
+}
+
+/// Whether a comma just before `bytes[pos]` is trailing: only whitespace and comments come
+/// between it and a `]` or `}`.
+fn is_trailing(bytes: &[u8], pos: usize) -> bool {
+    let mut state = Top;
+    for &c in &bytes[pos..] {
+        let Ok((next, _)) = transition(state, c, CommentSettings::all()) else {
+            return false;
+        };
+        if state == Top && !matches!(next, InComment | InLineComment) && !c.is_ascii_whitespace() {
+            return matches!(c, b']' | b'}');
+        }
+        state = next;
+    }
+    false
+}
+
+impl<'a> Iterator for StripChunks<'a> {
+    type Item = Result<Cow<'a, str>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        const SPACES: &str = "                                                                ";
+
+        if let Some(removed) = self.removed.take() {
+            self.last = removed.end;
+            return Some(Ok(match SPACES.get(..removed.len()) {
+                Some(spaces) => Cow::Borrowed(spaces),
+                None => Cow::Owned(" ".repeat(removed.len())),
+            }));
+        }
+        if let Some(err) = self.error.take() {
+            self.last = self.s.len();
+            return Some(Err(err.into()));
+        }
+        let end = match self.next_removal() {
+            Ok(Some(removed)) => {
+                let start = removed.start;
+                self.removed = Some(removed);
+                start
+            }
+            Ok(None) => self.s.len(),
+            Err(err) => {
+                // The text up to the string or comment that failed is kept as usual.
+                self.pos = self.s.len();
+                self.state = Top;
+                self.error = Some(err);
+                self.start.max(self.last)
+            }
+        };
+        let kept = &self.s[self.last..end];
+        self.last = end;
+        if kept.is_empty() {
+            return if self.removed.is_some() || self.error.is_some() {
+                self.next()
+            } else {
+                None
+            };
+        }
+        Some(Ok(Cow::Borrowed(kept)))
+    }
+}
+
+/// Returns the fraction of bytes in `s` that belong to comments, from `0.0` to `1.0`.
+///
+/// Newlines that end line comments are not part of the comment. Empty input has a density of
+/// `0.0`.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::comment_density;
+///
+/// assert_eq!(comment_density(r#"{"a": 1}"#).unwrap(), 0.0);
+/// assert_eq!(comment_density("// abcdefg\n[1,2,3,4]").unwrap(), 0.5);
+/// ```
+pub fn comment_density(s: &str) -> Result<f64> {
+    if s.is_empty() {
+        return Ok(0.0);
+    }
+    let comment_bytes: usize = comment_ranges(s, CommentSettings::all())?
+        .iter()
+        .map(ExactSizeIterator::len)
+        .sum();
+    Ok(comment_bytes as f64 / s.len() as f64)
+}
+
+/// Replaces every comment with a numbered placeholder comment, returning the rewritten text
+/// together with the original comments.
+///
+/// The `n`th comment becomes `/*__C{n}__*/`, so the result is still valid input for this crate
+/// and can be stripped and parsed as usual. Trailing commas are kept. After editing, pass the
+/// text and the comments to [`restore_placeholders`] to put the original comments back.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{restore_placeholders, strip_with_placeholders};
+///
+/// let input = "{\n  // name\n  \"a\": 1 /* one */\n}";
+/// let (skeleton, comments) = strip_with_placeholders(input).unwrap();
+///
+/// assert_eq!(skeleton, "{\n  /*__C0__*/\n  \"a\": 1 /*__C1__*/\n}");
+/// assert_eq!(comments, ["// name", "/* one */"]);
+/// assert_eq!(restore_placeholders(&skeleton, &comments).unwrap(), input);
+/// ```
+pub fn strip_with_placeholders(s: &str) -> Result<(String, Vec<String>)> {
+    let mut out = String::with_capacity(s.len());
+    let mut comments = Vec::new();
+    let mut last = 0;
+    for range in comment_ranges(s, CommentSettings::all())? {
+        out.push_str(&s[last..range.start]);
+        out.push_str(&format!("/*__C{}__*/", comments.len()));
+        comments.push(s[range.clone()].to_owned());
+        last = range.end;
+    }
+    out.push_str(&s[last..]);
+    Ok((out, comments))
+}
+
+/// Puts back the comments replaced by [`strip_with_placeholders`].
+///
+/// Only real comments are considered, so placeholder-like text inside strings is left alone.
+/// Comments that are not placeholders, such as ones added while editing, are kept as they are.
+/// Errors if a placeholder refers to a comment that doesn't exist.
+pub fn restore_placeholders(s: &str, comments: &[String]) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    for range in comment_ranges(s, CommentSettings::all())? {
+        let Some(index) = s[range.clone()]
+            .strip_prefix("/*__C")
+            .and_then(|rest| rest.strip_suffix("__*/"))
+            .and_then(|index| index.parse::<usize>().ok())
+        else {
+            continue;
+        };
+        let comment = comments.get(index).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("placeholder {index} has no matching comment"),
+            )
+        })?;
+        out.push_str(&s[last..range.start]);
+        out.push_str(comment);
+        last = range.end;
+    }
+    out.push_str(&s[last..]);
+    Ok(out)
+}
+
+/// Options for [`strip_with_replacements`].
+///
+/// The default replaces everything with spaces.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ReplaceOptions {
+    /// The byte that fills `/* ... */` comments.
+    pub replacement_block: u8,
+    /// The byte that fills `// ...` comments.
+    pub replacement_line: u8,
+    /// The byte that fills `# ...` comments.
+    pub replacement_hash: u8,
+}
+
+impl Default for ReplaceOptions {
+    fn default() -> Self {
+        Self {
+            replacement_block: b' ',
+            replacement_line: b' ',
+            replacement_hash: b' ',
+        }
+    }
+}
+
+/// Like [`crate::strip`], but fills each kind of comment with its own byte, which makes it easy
+/// to see what was classified as which kind of comment.
+///
+/// Line breaks inside block comments are kept, and trailing commas become spaces. The
+/// replacement bytes must be ASCII, so that the text stays UTF-8, or an
+/// [`ErrorKind::InvalidInput`] error is returned before anything is changed. Unlike
+/// [`crate::strip`], an unterminated string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_with_replacements, ReplaceOptions};
+///
+/// let options = ReplaceOptions {
+///     replacement_block: b'.',
+///     replacement_line: b'_',
+///     replacement_hash: b'~',
+/// };
+/// let mut s = String::from("[1, /* a\nb */ 2,] // c\n# d");
+/// strip_with_replacements(&mut s, options).unwrap();
+/// assert_eq!(s, "[1, ....\n.... 2 ] ____\n~~~");
+/// ```
+pub fn strip_with_replacements(s: &mut str, options: ReplaceOptions) -> Result<()> {
+    let replacements = [
+        options.replacement_block,
+        options.replacement_line,
+        options.replacement_hash,
+    ];
+    if !replacements.iter().all(u8::is_ascii) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "replacement bytes must be ASCII",
+        ));
+    }
+    let removals = removals(s, CommentSettings::all())?;
+    // Safety: only ASCII bytes are written, and only over whole comments and commas, so the
+    // text stays UTF-8.
+    let buf = unsafe { s.as_bytes_mut() };
+    for range in removals.comments {
+        let comment = &mut buf[range];
+        if comment[0] == b'#' {
+            comment.fill(options.replacement_hash);
+        } else if comment[1] == b'/' {
+            comment.fill(options.replacement_line);
+        } else {
+            for c in comment.iter_mut().filter(|c| !matches!(c, b'\n' | b'\r')) {
+                *c = options.replacement_block;
+            }
+        }
+    }
+    for comma in removals.commas {
+        buf[comma] = b' ';
+    }
+    Ok(())
+}
+
+/// Rewrites JSONC as JSON5, keeping every comment and trailing comma.
+///
+/// JSON5 has no `#` comments, so each one is turned into a `//` comment with the same text.
+/// Everything else, including `//` and `/* */` comments, is copied unchanged. Each `#` comment
+/// grows by one byte, so offsets after it shift.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::to_json5;
+///
+/// let input = "{\n  # name\n  \"a\": \"#1\", /* one */\n}";
+/// assert_eq!(to_json5(input).unwrap(), "{\n  // name\n  \"a\": \"#1\", /* one */\n}");
+/// ```
+pub fn to_json5(s: &str) -> Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut last = 0;
+    for comment in comments(s) {
+        let (kind, range) = comment?;
+        if kind == CommentKind::Hash {
+            out.push_str(&s[last..range.start]);
+            out.push_str("//");
+            last = range.start + 1;
+        }
+    }
+    out.push_str(&s[last..]);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = r#"/** header */
+{
+    "a": "/*__C0__*/ // not a comment", // trailing
+    # hash
+    "b": [1, 2,], /* multi
+    line */
+} // eof"#;
+
+    #[test]
+    fn round_trip() {
+        let (skeleton, comments) = strip_with_placeholders(INPUT).unwrap();
+        assert_eq!(
+            skeleton,
+            r#"/*__C0__*/
+{
+    "a": "/*__C0__*/ // not a comment", /*__C1__*/
+    /*__C2__*/
+    "b": [1, 2,], /*__C3__*/
+} /*__C4__*/"#
+        );
+        assert_eq!(
+            comments,
+            [
+                "/** header */",
+                "// trailing",
+                "# hash",
+                "/* multi\n    line */",
+                "// eof"
+            ]
+        );
+        assert_eq!(restore_placeholders(&skeleton, &comments).unwrap(), INPUT);
+    }
+
+    #[test]
+    fn restore_after_edit() {
+        let (skeleton, comments) = strip_with_placeholders(INPUT).unwrap();
+        let edited = skeleton.replace("[1, 2,]", "[3] /* new */");
+        assert_eq!(
+            restore_placeholders(&edited, &comments).unwrap(),
+            INPUT.replace("[1, 2,]", "[3] /* new */")
+        );
+    }
+
+    #[test]
+    fn missing_comment() {
+        let err = restore_placeholders("{} /*__C3__*/", &[]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let err = strip_with_placeholders("{} /* open").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn errors_carry_strip_error() {
+        use crate::StripErrorKind::*;
+        let strip_error = |err: Error| {
+            let err = err
+                .get_ref()
+                .and_then(|e| e.downcast_ref::<StripError>())
+                .copied();
+            err.map(|err| (err.kind(), err.offset()))
+        };
+        for (input, expected) in [
+            ("[1] /* open", (UnterminatedBlockComment, 4)),
+            ("[1, \"open", (UnterminatedString, 4)),
+            ("[1] /", (InvalidCommentStart, 5)),
+        ] {
+            let err = strip_compact(input).unwrap_err();
+            assert_eq!(strip_error(err), Some(expected), "{input:?}");
+            let err = comments(input).last().unwrap().unwrap_err();
+            assert_eq!(strip_error(err), Some(expected), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn sourcemap() {
+        let (out, anchors) = strip_with_sourcemap(INPUT).unwrap();
+        assert_eq!(out, crate::strip_str(INPUT).unwrap());
+        let ranges = removals(INPUT, CommentSettings::all()).unwrap().ranges();
+        assert_eq!(ranges.len(), 7);
+        let expected: Vec<_> = ranges
+            .iter()
+            .flat_map(|range| [(range.start, range.start), (range.end, range.end)])
+            .collect();
+        assert_eq!(anchors, expected);
+
+        let (out, anchors) = strip_with_sourcemap("[1,/**/]// a").unwrap();
+        assert_eq!(out, "[1     ]    ");
+        assert_eq!(anchors, [(2, 2), (3, 3), (7, 7), (8, 8), (12, 12)]);
+
+        let err = strip_with_sourcemap("[\"open // x").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn density() {
+        assert_eq!(comment_density("").unwrap(), 0.0);
+        assert_eq!(
+            comment_density(r#"{"a": "/* no */ // comments"}"#).unwrap(),
+            0.0
+        );
+        // 10 of 19 bytes.
+        assert_eq!(
+            comment_density("/* c */ [1,] # x\n{}").unwrap(),
+            10.0 / 19.0
+        );
+        assert_eq!(comment_density("// all").unwrap(), 1.0);
+        comment_density("/* open").unwrap_err();
+    }
+
+    #[test]
+    fn compact_keeps_line_numbers() {
+        let input =
+            "{\n  /* a\n     block */ \"a\": [1, 2, # x\n  ],\n  // line\n  \"b\": 3, /**/\n}\n";
+        let compact = strip_compact(input).unwrap();
+        assert_eq!(compact, "{\n\n \"a\": [1, 2\n  ],\n\n  \"b\": 3\n}\n");
+        assert_eq!(compact.lines().count(), input.lines().count());
+        assert!(!compact.contains("  \n"));
+
+        let input = "[1, /* a\r\n b */\r\n 2]";
+        assert_eq!(strip_compact(input).unwrap(), "[1,\r\n\r\n 2]");
+        assert_eq!(strip_compact("[1 /* a */, 2]").unwrap(), "[1 , 2]");
+
+        // Tokens on either side of a comment are kept apart.
+        assert_eq!(strip_compact("[1/**/2]").unwrap(), "[1 2]");
+        assert_eq!(strip_compact("{\"a\": 1/* x */2}").unwrap(), "{\"a\": 1 2}");
+        assert_eq!(strip_compact("[true/**//**/-1.5]").unwrap(), "[true -1.5]");
+        assert_eq!(strip_compact("[1/* a\n */2]").unwrap(), "[1\n2]");
+        assert_eq!(strip_compact("[1/**/]").unwrap(), "[1]");
+    }
+
+    #[test]
+    fn compact_line_endings() {
+        let input = "[1, /* a\r\n b\r\n */\r\n \"x\r\ny\", # z\r\n 2]";
+        assert_eq!(
+            strip_compact_with_line_ending(input, LineEnding::Lf).unwrap(),
+            "[1,\n\n\r\n \"x\r\ny\",\r\n 2]"
+        );
+        assert_eq!(
+            strip_compact_with_line_ending("[1 /* a\nb\rc */]", LineEnding::CrLf).unwrap(),
+            "[1\r\n\r\n]"
+        );
+        assert_eq!(
+            strip_compact_with_line_ending("[1 /* a */]", LineEnding::CrLf).unwrap(),
+            strip_compact("[1 /* a */]").unwrap()
+        );
+    }
+
+    #[test]
+    fn chunks_concatenate_to_strip() {
+        let long_comment = format!("/*{}*/", "x".repeat(100));
+        let inputs = [
+            String::from(r#"{"a": [1,/**/], "b": 2, // c"#),
+            format!("{long_comment}[1, 2,]{long_comment}"),
+            String::from("no comments at all"),
+            String::new(),
+            String::from("# only"),
+            String::from("[1, // c\n /* d */ ] {\"a\": \",]\",} [1, /* e */ 2] [3,"),
+        ];
+        for input in inputs {
+            let chunks: Vec<_> = strip_chunks(&input).collect::<Result<_>>().unwrap();
+            assert!(chunks.iter().all(|chunk| !chunk.is_empty()));
+            let mut expected = input.clone();
+            crate::strip(&mut expected).unwrap();
+            assert_eq!(chunks.concat(), expected);
+        }
+        // The text before an error is yielded first, and the error ends the iteration.
+        let mut iter = strip_chunks("[1] /* open");
+        assert_eq!(iter.next().unwrap().unwrap(), "[1] ");
+        let err = iter.next().unwrap().unwrap_err();
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<StripError>());
+        assert_eq!(
+            err,
+            Some(&StripError::new(
+                crate::StripErrorKind::UnterminatedBlockComment,
+                4
+            ))
+        );
+        assert!(iter.next().is_none());
+
+        let chunks: Vec<_> = strip_chunks("[1] // c\n\"open").collect();
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(
+            chunks[..3].iter().flatten().collect::<Vec<_>>(),
+            ["[1] ", "    ", "\n"]
+        );
+        assert!(chunks[3].is_err());
+
+        let mut iter = strip_chunks("/x");
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn comments_iterator() {
+        let found: Vec<_> = comments(INPUT).collect::<Result<_>>().unwrap();
+        let texts: Vec<_> = found
+            .iter()
+            .map(|(_, range)| &INPUT[range.clone()])
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "/** header */",
+                "// trailing",
+                "# hash",
+                "/* multi\n    line */",
+                "// eof"
+            ]
+        );
+        let kinds: Vec<_> = found.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                CommentKind::Block,
+                CommentKind::Slash,
+                CommentKind::Hash,
+                CommentKind::Block,
+                CommentKind::Slash
+            ]
+        );
+        let ranges: Vec<_> = found.into_iter().map(|(_, range)| range).collect();
+        assert_eq!(
+            ranges,
+            comment_ranges(INPUT, CommentSettings::all()).unwrap()
+        );
+
+        // Comments before an error are still yielded, and the error ends the iteration.
+        let mut iter = comments("/* a */ [1, /x]");
+        assert_eq!(iter.next().unwrap().unwrap(), (CommentKind::Block, 0..7));
+        assert_eq!(
+            iter.next().unwrap().unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(iter.next().is_none());
+
+        let mut iter = comments("[] /* open");
+        iter.next().unwrap().unwrap_err();
+        assert!(iter.next().is_none());
+        assert!(comments("\"// string\"").next().is_none());
+    }
+
+    #[test]
+    fn plan_matches_strip() {
+        for input in [
+            INPUT,
+            "[1, 2,] # end",
+            "",
+            "{\"a\": \"// no\"}",
+            "/* é */ [\"ü\",]",
+        ] {
+            let plan = plan(input).unwrap();
+            let mut planned = String::from(input);
+            plan.apply(&mut planned).unwrap();
+            let mut stripped = String::from(input);
+            crate::strip(&mut stripped).unwrap();
+            assert_eq!(planned, stripped);
+        }
+
+        let input = "[1 /* a */,]";
+        let owned = String::from(input);
+        let plan = plan(&owned).unwrap();
+        assert_eq!(owned, input);
+        assert_eq!(plan.edits, [Edit::Comment(3..10), Edit::TrailingComma(10)]);
+
+        // A plan for a different string is rejected rather than splitting characters.
+        let mut other = String::from("[1 ééééé]");
+        assert_eq!(
+            plan.apply(&mut other).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(other, "[1 ééééé]");
+        plan.apply(&mut String::from("[1]")).unwrap_err();
+        super::plan("[1 /* open").unwrap_err();
+    }
+
+    #[test]
+    fn compact_trailing_comma_to_null() {
+        let options = CompactOptions {
+            trailing_comma_to_null: true,
+            ..CompactOptions::default()
+        };
+        let compact = |input| strip_compact_with_options(input, options).unwrap();
+        assert_eq!(compact("[1,2,]"), "[1,2,null]");
+        assert_eq!(
+            compact("[[1,], [2, /* c */ ], {\"a\": [],},]"),
+            "[[1,null], [2,null  ], {\"a\": []},null]"
+        );
+        assert_eq!(compact("[1, // one\n  2, // two\n]"), "[1,\n  2,null\n]");
+        assert_eq!(compact("{\"a\": 1,}"), "{\"a\": 1}");
+        assert_eq!(compact("[1, \"2,]\"]"), "[1, \"2,]\"]");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn preserve_matching_comments() {
+        let pattern = regex::Regex::new(r"^\s*(@ts-|eslint-)").unwrap();
+        let input = "{\n  /* eslint-disable */\n  \"a\": [1,], // @ts-ignore\n  # note\n}";
+        let mut s = String::from(input);
+        strip_preserving_comments_matching(&mut s, &pattern).unwrap();
+        assert_eq!(
+            s,
+            "{\n  /* eslint-disable */\n  \"a\": [1 ]  // @ts-ignore\n        \n}"
+        );
+
+        // Stripping again with the same pattern changes nothing.
+        let once = s.clone();
+        strip_preserving_comments_matching(&mut s, &pattern).unwrap();
+        assert_eq!(s, once);
+
+        let mut s = String::from("[1] /* open");
+        strip_preserving_comments_matching(&mut s, &pattern).unwrap_err();
+    }
+
+    #[test]
+    fn json5() {
+        assert_eq!(
+            to_json5(INPUT).unwrap(),
+            INPUT.replace("    # hash", "    // hash")
+        );
+        assert_eq!(to_json5("#a\n#b\n[1,] #").unwrap(), "//a\n//b\n[1,] //");
+        assert_eq!(to_json5("[\"#\", \"a#b\"]").unwrap(), "[\"#\", \"a#b\"]");
+
+        // Stripping the result with c-style comments only gives the same JSON.
+        let mut expected = String::from(INPUT);
+        crate::strip(&mut expected).unwrap();
+        let mut json5 = to_json5(INPUT).unwrap();
+        crate::strip_comments_in_place(&mut json5, CommentSettings::c_style()).unwrap();
+        assert_eq!(
+            json5.split_whitespace().collect::<String>(),
+            expected.split_whitespace().collect::<String>()
+        );
+
+        to_json5("[1] /* open").unwrap_err();
+    }
+
+    #[test]
+    fn replacements() {
+        let options = ReplaceOptions {
+            replacement_block: b'.',
+            replacement_line: b'_',
+            replacement_hash: b'~',
+        };
+        let mut s = String::from(INPUT);
+        strip_with_replacements(&mut s, options).unwrap();
+        assert_eq!(
+            s,
+            r#".............
+{
+    "a": "/*__C0__*/ // not a comment", ___________
+    ~~~~~~
+    "b": [1, 2 ]  ........
+...........
+} ______"#
+        );
+
+        let mut s = String::from(INPUT);
+        strip_with_replacements(&mut s, ReplaceOptions::default()).unwrap();
+        let mut expected = String::from(INPUT);
+        crate::strip(&mut expected).unwrap();
+        // The same as stripping, except for the line break kept in the block comment.
+        assert_eq!(s.replace('\n', " "), expected.replace('\n', " "));
+        assert_eq!(s.lines().count(), INPUT.lines().count());
+
+        let mut s = String::from("[1] /* a\r\n*/ # é");
+        strip_with_replacements(&mut s, options).unwrap();
+        assert_eq!(s, "[1] ....\r\n.. ~~~~");
+
+        let non_ascii = ReplaceOptions {
+            replacement_line: 0xe9,
+            ..options
+        };
+        let mut s = String::from("[1] // c");
+        let err = strip_with_replacements(&mut s, non_ascii).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(s, "[1] // c");
+    }
+
+    #[test]
+    fn preserve_comments_in_context() {
+        let input = "// top\n{\"a\": /* obj */ [1, /* arr */ {\"b\": 2 # inner obj\n}], \"[\": \"{ // s\",}";
+        let strip_in = |context| {
+            let mut s = String::from(input);
+            strip_preserving_comments_in(&mut s, context).unwrap();
+            s
+        };
+
+        assert_eq!(
+            strip_in(Context::Objects),
+            "      \n{\"a\": /* obj */ [1,           {\"b\": 2 # inner obj\n}], \"[\": \"{ // s\" }"
+        );
+        assert_eq!(
+            strip_in(Context::Arrays),
+            "      \n{\"a\":           [1, /* arr */ {\"b\": 2            \n}], \"[\": \"{ // s\" }"
+        );
+        assert_eq!(
+            strip_in(Context::Both),
+            "      \n{\"a\": /* obj */ [1, /* arr */ {\"b\": 2 # inner obj\n}], \"[\": \"{ // s\" }"
+        );
+        let mut stripped = String::from(input);
+        crate::strip(&mut stripped).unwrap();
+        assert_eq!(strip_in(Context::Neither), stripped);
+
+        let mut s = String::from("[1, \"\\\"]\" /* kept */]");
+        strip_preserving_comments_in(&mut s, Context::Arrays).unwrap();
+        assert_eq!(s, "[1, \"\\\"]\" /* kept */]");
+
+        let mut s = String::from("[\"open");
+        strip_preserving_comments_in(&mut s, Context::Both).unwrap_err();
+    }
+
+    #[test]
+    fn lsp_text_edits() {
+        for input in [INPUT, "[\"é\", /* ü\r\n */ 1,] # ø", "{}", ""] {
+            let edits = text_edits(input).unwrap();
+            let mut applied = String::from(input);
+            // Applying from the end keeps earlier ranges valid even if lengths changed.
+            for edit in edits.iter().rev() {
+                applied.replace_range(edit.range.clone(), &edit.new_text);
+            }
+            let mut expected = String::from(input);
+            crate::strip(&mut expected).unwrap();
+            assert_eq!(applied, expected);
+        }
+
+        assert_eq!(text_edits(r#"{"a": "// no"}"#).unwrap(), []);
+        text_edits("[1] /* open").unwrap_err();
+    }
+
+    #[test]
+    fn strip_with_comment_sink() {
+        let mut s = String::from(INPUT);
+        let mut seen = Vec::new();
+        strip_with_comments(&mut s, |kind, range, text| {
+            assert_eq!(&INPUT[range.clone()], text);
+            seen.push((kind, range.start, text.to_owned()));
+        })
+        .unwrap();
+        assert_eq!(
+            seen,
+            [
+                (CommentKind::Block, 0, "/** header */".to_owned()),
+                (CommentKind::Slash, 56, "// trailing".to_owned()),
+                (CommentKind::Hash, 72, "# hash".to_owned()),
+                (CommentKind::Block, 97, "/* multi\n    line */".to_owned()),
+                (CommentKind::Slash, 120, "// eof".to_owned()),
+            ]
+        );
+        assert_eq!(s, crate::strip_str(INPUT).unwrap());
+
+        let mut s = String::from("[1] // a\n/* open");
+        let mut count = 0;
+        strip_with_comments(&mut s, |_, _, _| count += 1).unwrap_err();
+        assert_eq!((count, s.as_str()), (1, "[1] // a\n/* open"));
+    }
+
+    #[test]
+    fn preserve_comments_with_prefix() {
+        let input = "/** @license MIT */\n/**! x */ [1, /*!*/ 2, /* ! */ 3,] // ! y\n/*";
+        let mut s = String::from(input);
+        strip_preserving_comments_with_prefix(&mut s, "*!").unwrap_err();
+        assert_eq!(s, input);
+
+        let input = &input[..input.len() - 3];
+        let mut s = String::from(input);
+        strip_preserving_comments_with_prefix(&mut s, "*!").unwrap();
+        assert_eq!(
+            s,
+            "                   \n/**! x */ [1,       2,         3 ]       "
+        );
+
+        let mut s = String::from(input);
+        strip_preserving_comments_with_prefix(&mut s, "* @license").unwrap();
+        assert_eq!(
+            s,
+            "/** @license MIT */\n          [1,       2,         3 ]       "
+        );
+
+        // An empty prefix keeps every block comment.
+        let mut s = String::from(input);
+        strip_preserving_comments_with_prefix(&mut s, "").unwrap();
+        assert_eq!(
+            s,
+            "/** @license MIT */\n/**! x */ [1, /*!*/ 2, /* ! */ 3 ]       "
+        );
+
+        // The prefix may be built at runtime.
+        let prefix = String::from("* @") + "license";
+        let mut s = String::from(input);
+        strip_preserving_comments_with_prefix(&mut s, &prefix).unwrap();
+        assert!(s.starts_with("/** @license MIT */\n "));
+
+        for prefix in ["x */", "!!!!!!!!!!!!!!!!!"] {
+            let mut s = String::from(input);
+            let err = strip_preserving_comments_with_prefix(&mut s, prefix).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            assert_eq!(s, input);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! The structured error behind [`ErrorKind::InvalidData`] failures.
+
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{Error, ErrorKind};
+
+use crate::State::{
+    self, BacktickEscape, InBacktickString, InSingleQuoteString, InString, SingleQuoteEscape,
+    StringEscape,
+};
+
+/// Why stripping failed, and where.
+///
+/// The `io::Error`s returned for invalid input carry a `StripError` as their payload, which can
+/// be recovered with a downcast.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{StripComments, StripError, StripErrorKind};
+/// use std::io::Read;
+///
+/// let mut reader = StripComments::new("{\"a\": 1} /* open".as_bytes());
+/// let err = reader.read_to_string(&mut String::new()).unwrap_err();
+/// let err = err.get_ref().and_then(|e| e.downcast_ref::<StripError>()).unwrap();
+///
+/// assert_eq!(err.kind(), StripErrorKind::UnterminatedBlockComment);
+/// assert_eq!(err.offset(), 9);
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StripError {
+    kind: StripErrorKind,
+    offset: usize,
+}
+
+/// The cause of a [`StripError`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum StripErrorKind {
+    /// A `/` was followed by something other than `/` or `*`. The offset is of the byte after
+    /// the `/`, which is the end of the input if the `/` came last.
+    InvalidCommentStart,
+    /// A string contained a raw control character, with
+    /// [`CommentSettings::forbid_control_chars_in_strings`](crate::CommentSettings::forbid_control_chars_in_strings).
+    /// The offset is of the character.
+    ControlCharInString,
+    /// The input ended inside a string. The offset is of its opening quote.
+    UnterminatedString,
+    /// The input ended inside a block comment. The offset is of its opening `/*`.
+    UnterminatedBlockComment,
+    /// Block comments were nested more than [`MAX_COMMENT_NESTING`](crate::MAX_COMMENT_NESTING)
+    /// deep, with
+    /// [`CommentSettings::nested_block_comments`](crate::CommentSettings::nested_block_comments).
+    /// The offset is of the `*` of the `/*` that went too deep.
+    CommentNestingTooDeep,
+    /// A comment was found by [`check`](crate::check). The offset is of its first byte.
+    Comment,
+    /// A trailing comma was found by [`check`](crate::check). The offset is of the comma.
+    TrailingComma,
+    /// A comma between top-level values was found with
+    /// [`TopLevelCommas::Error`](crate::TopLevelCommas::Error). The offset is of the comma.
+    TopLevelComma,
+}
+
+impl StripError {
+    pub(crate) fn new(kind: StripErrorKind, offset: usize) -> Self {
+        Self { kind, offset }
+    }
+
+    /// The error for input that ends in `state`, where the string or comment being read began at
+    /// `start`.
+    pub(crate) fn at_eof(state: State, start: usize, len: usize) -> Self {
+        match state {
+            InString | StringEscape | InBacktickString | BacktickEscape | InSingleQuoteString
+            | SingleQuoteEscape => Self::new(StripErrorKind::UnterminatedString, start),
+            State::InComment => Self::new(StripErrorKind::InvalidCommentStart, len),
+            _ => Self::new(StripErrorKind::UnterminatedBlockComment, start),
+        }
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> StripErrorKind {
+        self.kind
+    }
+
+    /// The byte offset in the input where it went wrong. See [`StripErrorKind`] for which byte
+    /// each kind points at.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The 1-based line and column of [`offset`](Self::offset) in `input`, which must be the
+    /// whole input that was stripped.
+    ///
+    /// Lines end at `\n`, `\r\n`, or a lone `\r`, as line comments do, and columns count
+    /// characters, not bytes. The position is found by rescanning `input`, so readers don't pay
+    /// for tracking it unless an error is reported.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::{strip_comments_in_place, CommentSettings, StripError};
+    ///
+    /// let input = "{\n  \"é\": 1, / oops\n}";
+    /// let err = strip_comments_in_place(&mut input.to_owned(), CommentSettings::all()).unwrap_err();
+    /// let err = err.get_ref().and_then(|e| e.downcast_ref::<StripError>()).unwrap();
+    ///
+    /// assert_eq!(err.line_column(input.as_bytes()), (2, 12));
+    /// ```
+    pub fn line_column(&self, input: &[u8]) -> (usize, usize) {
+        let before = &input[..self.offset.min(input.len())];
+        let ends_line = |&i: &usize| match before[i] {
+            b'\n' => true,
+            b'\r' => input.get(i + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        let line_start = (0..before.len()).rfind(ends_line).map_or(0, |i| i + 1);
+        let line = 1 + (0..before.len()).filter(ends_line).count();
+        // Count the bytes that start a UTF-8 character, skipping continuation bytes.
+        let column = 1 + before[line_start..]
+            .iter()
+            .filter(|&&c| c & 0xC0 != 0x80)
+            .count();
+        (line, column)
+    }
+}
+
+impl fmt::Display for StripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            StripErrorKind::InvalidCommentStart => "invalid comment start",
+            StripErrorKind::ControlCharInString => "raw control character in string",
+            StripErrorKind::UnterminatedString => "unterminated string",
+            StripErrorKind::UnterminatedBlockComment => "unterminated block comment",
+            StripErrorKind::CommentNestingTooDeep => "block comments nested too deep",
+            StripErrorKind::Comment => "comment",
+            StripErrorKind::TrailingComma => "trailing comma",
+            StripErrorKind::TopLevelComma => "comma between top-level values",
+        };
+        write!(f, "{message} at offset {}", self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StripError {}
+
+#[cfg(feature = "std")]
+impl From<StripError> for Error {
+    fn from(err: StripError) -> Self {
+        Error::new(ErrorKind::InvalidData, err)
+    }
+}
+
+/// A key that appears twice in the same object, found by
+/// [`strip_and_reject_duplicate_keys`](crate::strip_and_reject_duplicate_keys).
+///
+/// Like [`StripError`], it is the payload of an [`ErrorKind::InvalidData`] error.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateKeyError {
+    key: String,
+    offset: usize,
+}
+
+#[cfg(feature = "std")]
+impl DuplicateKeyError {
+    pub(crate) fn new(key: String, offset: usize) -> Self {
+        Self { key, offset }
+    }
+
+    /// The key as written between its quotes, with any escapes left as they are.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The byte offset of the opening quote of the second occurrence.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key \"{}\" at offset {}",
+            self.key, self.offset
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DuplicateKeyError {}
+
+#[cfg(feature = "std")]
+impl From<DuplicateKeyError> for Error {
+    fn from(err: DuplicateKeyError) -> Self {
+        Error::new(ErrorKind::InvalidData, err)
+    }
+}
+
+/// Moves the offset of a [`StripError`] inside `err` forward by `base`, for errors found in a
+/// buffer that starts `base` bytes into the input.
+#[cfg(feature = "std")]
+#[cold]
+#[inline(never)]
+pub(crate) fn offset_by(err: Error, base: usize) -> Error {
+    match err.get_ref().and_then(|e| e.downcast_ref::<StripError>()) {
+        Some(&StripError { kind, offset }) => StripError::new(kind, base + offset).into(),
+        None => err,
+    }
+}
@@ -0,0 +1,606 @@
+//! Helpers built on top of [serde_json](https://crates.io/crates/serde_json).
+//!
+//! Enabled with the `serde` feature.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{BufReader, Error, ErrorKind, Read, Result},
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "schema")]
+use std::borrow::Cow;
+
+#[cfg(feature = "schema")]
+use jsonschema::{paths::JSONPointer, JSONSchema, ValidationError};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{strip, strip_comments_in_place_strict, CommentSettings, StripComments, StripError};
+
+/// Strips comments and trailing commas, then re-serializes the JSON with object keys sorted.
+///
+/// Keys are sorted recursively while array elements keep their order, so the output is
+/// canonical regardless of how the input was laid out or commented.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_and_sort_keys;
+///
+/// let input = r#"{
+///   "b": 1, // second
+///   "a": { "d": [3, 2], "c": true, },
+/// }"#;
+///
+/// assert_eq!(strip_and_sort_keys(input).unwrap(), r#"{"a":{"c":true,"d":[3,2]},"b":1}"#);
+/// ```
+pub fn strip_and_sort_keys(s: &str) -> Result<String> {
+    let mut s = s.to_owned();
+    strip(&mut s)?;
+    let value: Value = serde_json::from_str(&s)?;
+    Ok(serde_json::to_string(&sort_keys(value))?)
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            // Collect through a `BTreeMap` so the order holds even when serde_json's
+            // `preserve_order` feature is enabled elsewhere in the dependency graph.
+            let sorted: BTreeMap<String, Value> =
+                map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+/// Strips comments and trailing commas, then converts the JSON into another format by driving
+/// `serializer` directly, without building an intermediate [`Value`].
+///
+/// Enabled with the `transcode` feature. Invalid JSON is reported as [`ErrorKind::InvalidData`]
+/// and serializer errors are converted to [`ErrorKind::Other`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::transcode_stripped;
+///
+/// let mut out = Vec::new();
+/// let mut serializer = serde_json::Serializer::pretty(&mut out);
+/// transcode_stripped("{\"a\": [1, 2,], // c\n}", &mut serializer).unwrap();
+///
+/// assert_eq!(String::from_utf8(out).unwrap(), "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+/// ```
+#[cfg(feature = "transcode")]
+pub fn transcode_stripped<S: serde::Serializer>(input: &str, serializer: S) -> Result<S::Ok> {
+    let mut input = input.to_owned();
+    strip(&mut input)?;
+    // `serde_transcode` reports deserializer errors through the serializer's error type, so
+    // check the JSON first to keep the two apart.
+    serde_json::from_str::<serde::de::IgnoredAny>(&input)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+    let mut deserializer = serde_json::Deserializer::from_str(&input);
+    serde_transcode::transcode(&mut deserializer, serializer)
+        .map_err(|err| Error::other(err.to_string()))
+}
+
+/// Strips comments and trailing commas, parses the JSON, and validates it against `schema`.
+///
+/// Enabled with the `schema` feature. Every problem is reported as a [`ValidationError`]:
+/// invalid comments as [`ValidationErrorKind::Custom`], invalid JSON as
+/// [`ValidationErrorKind::JSONParse`], and an invalid schema as the error from compiling it.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_and_validate_schema;
+/// use serde_json::json;
+///
+/// let schema = json!({"type": "object", "required": ["name"]});
+///
+/// assert!(strip_and_validate_schema("{\"name\": \"a\", // ok\n}", &schema).is_ok());
+///
+/// let errors = strip_and_validate_schema("{/* no name */}", &schema).unwrap_err();
+/// assert_eq!(errors[0].to_string(), "\"name\" is a required property");
+/// ```
+///
+/// [`ValidationErrorKind::Custom`]: jsonschema::error::ValidationErrorKind::Custom
+/// [`ValidationErrorKind::JSONParse`]: jsonschema::error::ValidationErrorKind::JSONParse
+#[cfg(feature = "schema")]
+pub fn strip_and_validate_schema(
+    s: &str,
+    schema: &Value,
+) -> std::result::Result<(), Vec<ValidationError<'static>>> {
+    use jsonschema::error::ValidationErrorKind;
+
+    let error = |kind| ValidationError {
+        instance: Cow::Owned(Value::Null),
+        kind,
+        instance_path: JSONPointer::default(),
+        schema_path: JSONPointer::default(),
+    };
+    let mut s = s.to_owned();
+    strip(&mut s).map_err(|err| {
+        vec![error(ValidationErrorKind::Custom {
+            message: format!("failed to strip comments: {err}"),
+        })]
+    })?;
+    let instance: Value = serde_json::from_str(&s)
+        .map_err(|err| vec![error(ValidationErrorKind::JSONParse { error: err })])?;
+    let compiled = JSONSchema::compile(schema).map_err(|err| vec![into_owned(err)])?;
+    compiled
+        .validate(&instance)
+        .map_err(|errors| errors.map(into_owned).collect())
+}
+
+/// Detaches a validation error from the instance it borrows.
+#[cfg(feature = "schema")]
+fn into_owned(err: ValidationError<'_>) -> ValidationError<'static> {
+    ValidationError {
+        instance: Cow::Owned(err.instance.into_owned()),
+        kind: err.kind,
+        instance_path: err.instance_path,
+        schema_path: err.schema_path,
+    }
+}
+
+/// Reads a JSON file with comments and trailing commas and deserializes it into a `T`.
+///
+/// Enabled with the `serde` feature.
+///
+/// ## Example
+/// ```
+/// # let path = std::env::temp_dir().join("doc-load-file.jsonc");
+/// # std::fs::write(&path, "{ \"port\": 8080, // default\n }").unwrap();
+/// use std::collections::HashMap;
+///
+/// let config: HashMap<String, u16> = json_strip_comments::load_file(&path).unwrap();
+/// assert_eq!(config["port"], 8080);
+/// ```
+pub fn load_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> std::result::Result<T, LoadError> {
+    let path = path.as_ref();
+    let file = fs::File::open(path).map_err(|source| LoadError::Io {
+        path: path.to_owned(),
+        source,
+    })?;
+    from_jsonc_reader(file).map_err(|err| {
+        let path = path.to_owned();
+        match err {
+            FromJsoncError::Io(source) => LoadError::Io { path, source },
+            FromJsoncError::Strip(source) => LoadError::Strip { path, source },
+            FromJsoncError::Json(source) => LoadError::Json { path, source },
+        }
+    })
+}
+
+/// Deserializes a `T` from JSON with comments and trailing commas.
+///
+/// Stripping keeps every byte offset, so the line and column of a
+/// [`FromJsoncError::Json`] point into `input`.
+///
+/// Enabled with the `serde` feature.
+///
+/// ## Example
+/// ```
+/// use std::collections::HashMap;
+///
+/// let input = "{ \"port\": 8080, /* default */ }";
+/// let config: HashMap<String, u16> = json_strip_comments::from_jsonc(input).unwrap();
+/// assert_eq!(config["port"], 8080);
+/// ```
+pub fn from_jsonc<T: DeserializeOwned>(input: &str) -> std::result::Result<T, FromJsoncError> {
+    let mut s = input.to_owned();
+    strip_comments_in_place_strict(&mut s, CommentSettings::default())
+        .map_err(FromJsoncError::from_io)?;
+    serde_json::from_str(&s).map_err(FromJsoncError::Json)
+}
+
+/// Deserializes a `T` from a reader of JSON with comments and trailing commas, stripping it
+/// with [`StripComments`] as it is read.
+///
+/// Enabled with the `serde` feature.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{from_jsonc_reader, FromJsoncError};
+///
+/// let numbers: Vec<u32> = from_jsonc_reader("[1, 2, # three\n]".as_bytes()).unwrap();
+/// assert_eq!(numbers, [1, 2]);
+///
+/// let err = from_jsonc_reader::<Vec<u32>, _>("[1, /x]".as_bytes()).unwrap_err();
+/// assert!(matches!(err, FromJsoncError::Strip(_)));
+/// ```
+pub fn from_jsonc_reader<T: DeserializeOwned, R: Read>(
+    reader: R,
+) -> std::result::Result<T, FromJsoncError> {
+    // serde_json reads a byte at a time, so buffer the stripped output.
+    let reader = BufReader::new(StripComments::new(reader));
+    serde_json::from_reader(reader).map_err(FromJsoncError::from)
+}
+
+/// The error returned by [`from_jsonc`] and [`from_jsonc_reader`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FromJsoncError {
+    /// The input could not be read.
+    Io(Error),
+    /// The input has invalid comments or an unterminated string or comment.
+    Strip(StripError),
+    /// The stripped input is not valid JSON for the target type.
+    Json(serde_json::Error),
+}
+
+impl FromJsoncError {
+    fn from_io(err: Error) -> Self {
+        match err.get_ref().and_then(|e| e.downcast_ref::<StripError>()) {
+            Some(&err) => Self::Strip(err),
+            None => Self::Io(err),
+        }
+    }
+}
+
+impl From<serde_json::Error> for FromJsoncError {
+    fn from(err: serde_json::Error) -> Self {
+        if err.is_io() {
+            Self::from_io(err.into())
+        } else {
+            Self::Json(err)
+        }
+    }
+}
+
+impl fmt::Display for FromJsoncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(source) => write!(f, "failed to read: {source}"),
+            Self::Strip(source) => write!(f, "failed to strip: {source}"),
+            Self::Json(source) => write!(f, "invalid JSON: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for FromJsoncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(source) => Some(source),
+            Self::Strip(source) => Some(source),
+            Self::Json(source) => Some(source),
+        }
+    }
+}
+
+/// The error returned by [`load_file`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadError {
+    /// The file could not be read.
+    Io { path: PathBuf, source: Error },
+    /// The file has invalid comments or an unterminated string.
+    Strip { path: PathBuf, source: StripError },
+    /// The stripped file is not valid JSON for the target type.
+    Json {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+}
+
+impl LoadError {
+    /// The file that failed to load.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. } | Self::Strip { path, .. } | Self::Json { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+        match self {
+            Self::Io { source, .. } => write!(f, "failed to read {path}: {source}"),
+            Self::Strip { source, .. } => write!(f, "failed to strip {path}: {source}"),
+            Self::Json { source, .. } => write!(f, "invalid JSON in {path}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Strip { source, .. } => Some(source),
+            Self::Json { source, .. } => Some(source),
+        }
+    }
+}
+
+impl CommentSettings {
+    /// Reads settings from a JSON config file such as `.jsonstriprc`.
+    ///
+    /// The file may itself contain comments and trailing commas. It must contain an object whose
+    /// keys are the camelCase names of the settings, e.g. `hashLineComments`, each set to a
+    /// boolean. Missing keys keep their [`Default`] value, and unknown keys are an error.
+    ///
+    /// Enabled with the `serde` feature.
+    ///
+    /// ## Example
+    /// ```
+    /// # let path = std::env::temp_dir().join("doc.jsonstriprc");
+    /// # std::fs::write(&path, "{ \"hashLineComments\": false, // keep # lines\n }").unwrap();
+    /// use json_strip_comments::CommentSettings;
+    ///
+    /// let settings = CommentSettings::from_rc_file(&path).unwrap();
+    /// assert!(!settings.hash_line_comments);
+    /// assert!(settings.block_comments);
+    /// ```
+    pub fn from_rc_file(path: impl AsRef<Path>) -> Result<Self> {
+        let mut contents = fs::read_to_string(path)?;
+        strip(&mut contents)?;
+        let Value::Object(map) = serde_json::from_str(&contents)? else {
+            return Err(invalid_rc("expected an object"));
+        };
+
+        let mut settings = Self::default();
+        for (key, value) in map {
+            let field = match key.as_str() {
+                "blockComments" => &mut settings.block_comments,
+                "slashLineComments" => &mut settings.slash_line_comments,
+                "hashLineComments" => &mut settings.hash_line_comments,
+                "trailingCommas" => &mut settings.trailing_commas,
+                "elideEmptyElements" => &mut settings.elide_empty_elements,
+                "lineCommentContinuation" => &mut settings.line_comment_continuation,
+                "nestedBlockComments" => &mut settings.nested_block_comments,
+                "unicodeLineSeparators" => &mut settings.unicode_line_separators,
+                "loneSlashes" => &mut settings.lone_slashes,
+                "forbidControlCharsInStrings" => &mut settings.forbid_control_chars_in_strings,
+                "recoverUnterminatedStrings" => &mut settings.recover_unterminated_strings,
+                "backtickStrings" => &mut settings.backtick_strings,
+                "singleQuoteStrings" => &mut settings.single_quote_strings,
+                "skipBom" => &mut settings.skip_bom,
+                "stripLeadingShebang" => &mut settings.strip_leading_shebang,
+                _ => return Err(invalid_rc(&format!("unknown setting `{key}`"))),
+            };
+            *field = value
+                .as_bool()
+                .ok_or_else(|| invalid_rc(&format!("`{key}` must be a boolean")))?;
+        }
+        Ok(settings)
+    }
+}
+
+fn invalid_rc(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_rc(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("json-strip-comments-{name}.jsonstriprc"));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn sorts_nested_keys() {
+        let input = r#"{
+            "z": { "y": 1, "x": { "b": 2, "a": 1 } }, // nested
+            /* block */ "m": [ { "k": 1, "j": 2 } ],
+            "a": null,
+        }"#;
+        assert_eq!(
+            strip_and_sort_keys(input).unwrap(),
+            r#"{"a":null,"m":[{"j":2,"k":1}],"z":{"x":{"a":1,"b":2},"y":1}}"#
+        );
+    }
+
+    #[test]
+    fn arrays_keep_order() {
+        let input = r#"[3, 1, # comment
+            2, { "b": [ "z", "a" ], "a": 0 },]"#;
+        assert_eq!(
+            strip_and_sort_keys(input).unwrap(),
+            r#"[3,1,2,{"a":0,"b":["z","a"]}]"#
+        );
+    }
+
+    #[test]
+    fn invalid_json() {
+        let err = strip_and_sort_keys(r#"{"a": }"#).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rc_file() {
+        let path = write_rc(
+            "rc-file",
+            r#"{
+                // Our dialect uses `#` for directives.
+                "hashLineComments": false,
+                /* and wants trailing commas kept */
+                "trailingCommas": false,
+            }"#,
+        );
+        let settings = CommentSettings::from_rc_file(&path).unwrap();
+        assert!(settings.block_comments);
+        assert!(settings.slash_line_comments);
+        assert!(!settings.hash_line_comments);
+        assert!(!settings.trailing_commas);
+
+        let mut json = String::from("{\n# directive\n\"a\": [1,], // c\n}");
+        crate::strip_comments_in_place(&mut json, settings).unwrap();
+        assert_eq!(json, "{\n# directive\n\"a\": [1,],     \n}");
+    }
+
+    #[test]
+    fn rc_file_errors() {
+        let path = write_rc("unknown", r#"{ "hashComments": false }"#);
+        let err = CommentSettings::from_rc_file(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let path = write_rc("not-bool", r#"{ "blockComments": 1 }"#);
+        let err = CommentSettings::from_rc_file(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let path = write_rc("not-object", "[]");
+        let err = CommentSettings::from_rc_file(&path).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let err = CommentSettings::from_rc_file("does/not/exist").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn load_file_into_struct() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Config {
+            name: String,
+            ports: Vec<u16>,
+        }
+
+        let path = write_rc(
+            "load",
+            r#"{
+                // The service name.
+                "name": "api",
+                "ports": [80, 443,], /* both */
+            }"#,
+        );
+        let config: Config = load_file(&path).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                name: "api".into(),
+                ports: vec![80, 443]
+            }
+        );
+
+        let err = load_file::<Config>("does/not/exist").unwrap_err();
+        assert!(
+            matches!(&err, LoadError::Io { source, .. } if source.kind() == ErrorKind::NotFound)
+        );
+        assert_eq!(err.path(), Path::new("does/not/exist"));
+
+        let path = write_rc("load-bad-json", r#"{"name": "api", "ports": 80} // x"#);
+        let err = load_file::<Config>(&path).unwrap_err();
+        assert!(matches!(err, LoadError::Json { .. }));
+        assert!(err.to_string().starts_with("invalid JSON in "));
+
+        let path = write_rc("load-bad-comment", "{\"name\": /* open");
+        let err = load_file::<Config>(&path).unwrap_err();
+        assert!(matches!(err, LoadError::Strip { source, .. } if source.offset() == 9));
+    }
+
+    #[test]
+    fn from_jsonc_str_and_reader() {
+        let input = "{\"a\": [1, 2,], // x\n \"b\": \"/* kept */\"}";
+        let expected = serde_json::json!({"a": [1, 2], "b": "/* kept */"});
+        assert_eq!(from_jsonc::<Value>(input).unwrap(), expected);
+        assert_eq!(
+            from_jsonc_reader::<Value, _>(input.as_bytes()).unwrap(),
+            expected
+        );
+
+        for input in ["[1, /* open", "[1, /x]", "[\"open"] {
+            let err = from_jsonc::<Value>(input).unwrap_err();
+            assert!(matches!(err, FromJsoncError::Strip(_)), "{input}: {err}");
+            let err = from_jsonc_reader::<Value, _>(input.as_bytes()).unwrap_err();
+            assert!(matches!(err, FromJsoncError::Strip(_)), "{input}: {err}");
+        }
+
+        // Errors from serde_json point into the unstripped input.
+        let err = from_jsonc::<Value>("{ // x\n \"a\": } ").unwrap_err();
+        let FromJsoncError::Json(err) = err else {
+            panic!("{err}");
+        };
+        assert_eq!((err.line(), err.column()), (2, 7));
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn transcode_to_message_pack() {
+        let input = r#"{
+            "name": /* full */ "John Doe",
+            "age": 43, # hash comment
+            "phones": [
+                "+44 1234567", // work phone
+                "+44 2345678", // home phone
+            ],
+        }"#;
+        let mut out = Vec::new();
+        transcode_stripped(input, &mut rmp_serde::Serializer::new(&mut out)).unwrap();
+
+        let value: Value = rmp_serde::from_slice(&out).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "John Doe",
+                "age": 43,
+                "phones": ["+44 1234567", "+44 2345678"],
+            })
+        );
+    }
+
+    #[cfg(feature = "transcode")]
+    #[test]
+    fn transcode_errors() {
+        let mut out = Vec::new();
+        let err =
+            transcode_stripped("{\"a\": }", &mut rmp_serde::Serializer::new(&mut out)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+        assert!(err.get_ref().unwrap().is::<serde_json::Error>());
+
+        let err =
+            transcode_stripped("{} {}", &mut rmp_serde::Serializer::new(&mut out)).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let mut full = [0u8; 2];
+        let err = transcode_stripped(
+            "{\"a\": 1, // c\n}",
+            &mut rmp_serde::Serializer::new(&mut full[..]),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    }
+
+    #[cfg(feature = "schema")]
+    #[test]
+    fn validate_schema() {
+        use jsonschema::error::ValidationErrorKind;
+
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "port": {"type": "integer", "maximum": 65535},
+                "hosts": {"type": "array", "items": {"type": "string"}},
+            },
+            "required": ["port"],
+        });
+        let config = r#"{
+            // where to listen
+            "port": 8080,
+            "hosts": ["a", "b",], /* trailing commas are fine */
+        }"#;
+        strip_and_validate_schema(config, &schema).unwrap();
+
+        let errors =
+            strip_and_validate_schema(r#"{"port": 70000, "hosts": [1]}"#, &schema).unwrap_err();
+        let mut paths: Vec<_> = errors
+            .iter()
+            .map(|err| err.instance_path.to_string())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, ["/hosts/0", "/port"]);
+
+        let errors = strip_and_validate_schema("{\"port\": 1 /x}", &schema).unwrap_err();
+        assert!(matches!(errors[0].kind, ValidationErrorKind::Custom { .. }));
+        let errors = strip_and_validate_schema("{\"port\": }", &schema).unwrap_err();
+        assert!(matches!(
+            errors[0].kind,
+            ValidationErrorKind::JSONParse { .. }
+        ));
+        let bad_schema = serde_json::json!({"type": 1});
+        strip_and_validate_schema("{}", &bad_schema).unwrap_err();
+    }
+}
@@ -8,40 +8,152 @@
 //! `json-strip-comments` is a library to strip out comments from JSON. By processing text
 //! through a [`StripComments`] adapter first, it is possible to use a standard JSON parser (such
 //! as [serde_json](https://crates.io/crates/serde_json) with quasi-json input that contains
-//! comments.
+//! comments. With the `serde` feature, `from_jsonc` and `from_jsonc_reader` do both steps in
+//! one call.
 //!
 //! In fact, this code makes few assumptions about the input and could probably be used to strip
-//! comments out of other types of code as well, provided that strings use double quotes and
-//! backslashes are used for escapes in strings.
+//! comments out of other types of code as well, provided that backslashes are used for escapes
+//! in strings. Strings use double quotes unless
+//! [`string_delimiter`](CommentSettings::string_delimiter) says otherwise.
 //!
 //! The following types of comments are supported:
 //!   - C style block comments (`/* ... */`)
 //!   - C style line comments (`// ...`)
 //!   - Shell style line comments (`# ...`)
 //!
+//! Comments and trailing commas are replaced with spaces, so byte offsets into the stripped text
+//! match the original. For output meant to be read again, such as a cleaned-up config file,
+//! [`strip_compact`] removes them instead, keeping only the line breaks inside comments.
+//!
+//! ## Encodings
+//!
+//! Stripping only looks at ASCII bytes and only ever replaces bytes with ASCII spaces, so it
+//! works on any ASCII-compatible encoding, such as UTF-8, Latin-1, or Windows-1252.
+//! [`StripComments`], [`strip_bytes`], and [`strip_bytes_latin1`] accept arbitrary bytes.
+//! Functions taking `str` require UTF-8, and UTF-16 is not supported.
+//!
+//! ## `no_std`
+//!
+//! Everything but [`strip_slice`], [`strip_slice_strict`], [`strip_slice_stateful`],
+//! [`StripState`], [`CommentSettings`], and [`StripError`] needs the default `std` feature.
+//! Without it, the crate is `no_std` and needs no allocator.
+//!
 //! ## Example
 //!
 //! ```rust
 #![doc = include_str!("../examples/example.rs")]
 //! ```
 
-use std::io::{ErrorKind, Read, Result};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+mod comments;
+mod error;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "std")]
+pub mod sindresorhus_compat;
+#[cfg(feature = "std")]
+mod structure;
+#[cfg(feature = "std")]
+mod writer;
+
+#[cfg(feature = "std")]
+use std::{
+    borrow::Cow,
+    io::{BufRead, Error, ErrorKind, Lines, Read, Result},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+#[cfg(feature = "regex")]
+pub use comments::strip_preserving_comments_matching;
+#[cfg(feature = "std")]
+pub use comments::{
+    comment_density, comments, plan, restore_placeholders, strip_chunks, strip_compact,
+    strip_compact_with_line_ending, strip_compact_with_options, strip_preserving_comments_in,
+    strip_preserving_comments_with_prefix, strip_with_comments, strip_with_placeholders,
+    strip_with_replacements, strip_with_sourcemap, text_edits, to_json5, Comments, CompactOptions,
+    Context, Edit, LineEnding, ReplaceOptions, StripChunks, StripPlan, TextEdit,
+};
+#[cfg(feature = "std")]
+pub use error::DuplicateKeyError;
+pub use error::{StripError, StripErrorKind};
+#[cfg(feature = "schema")]
+pub use json::strip_and_validate_schema;
+#[cfg(feature = "transcode")]
+pub use json::transcode_stripped;
+#[cfg(feature = "serde")]
+pub use json::{
+    from_jsonc, from_jsonc_reader, load_file, strip_and_sort_keys, FromJsoncError, LoadError,
+};
+#[cfg(feature = "std")]
+pub use structure::{
+    max_depth_reached, significant_range, strip_and_reject_duplicate_keys,
+    strip_and_require_single_value, strip_single_value_in_place,
+    strip_single_value_in_place_with_options, SingleValueOptions,
+};
+#[cfg(feature = "std")]
+pub use writer::StripCommentsWriter;
 
 #[derive(Eq, PartialEq, Copy, Clone, Debug)]
 enum State {
     Top,
     InString,
     StringEscape,
+    InBacktickString,
+    BacktickEscape,
+    InSingleQuoteString,
+    SingleQuoteEscape,
     InComment,
     InBlockComment,
     MaybeCommentEnd,
+    /// Inside a block comment with [`CommentSettings::nested_block_comments`], at a depth of at
+    /// least one.
+    InNestedComment(u16),
+    /// After a `*` in a nested block comment.
+    MaybeNestedCommentEnd(u16),
+    /// After a `/` in a nested block comment.
+    MaybeNestedCommentStart(u16),
+    /// After the `/*` of a block comment and the first so many bytes of
+    /// [`CommentSettings::preserve_comment_prefix`], all blanked until the rest of the prefix
+    /// shows whether the comment is kept.
+    MaybePreservedComment(u16),
+    /// Inside a block comment kept with [`CommentSettings::preserve_comment_prefix`].
+    InPreservedComment,
+    /// After a `*` in a kept block comment.
+    MaybePreservedCommentEnd,
     InLineComment,
+    LineCommentContinuation,
+    /// After the first one or two bytes of what may be the UTF-8 encoding of U+2028 or U+2029
+    /// in a line comment, with [`CommentSettings::unicode_line_separators`].
+    MaybeLineSeparator(u8),
+}
+
+impl State {
+    /// Whether input may end in this state: at the top level, in a line comment, which the end
+    /// of the input closes, or after a `/` kept with [`CommentSettings::lone_slashes`].
+    fn is_complete(self, settings: CommentSettings) -> bool {
+        match self {
+            Top | InLineComment | LineCommentContinuation | MaybeLineSeparator(_) => true,
+            InComment => settings.lone_slashes,
+            _ => false,
+        }
+    }
 }
 
 use State::{
-    InBlockComment, InComment, InLineComment, InString, MaybeCommentEnd, StringEscape, Top,
+    BacktickEscape, InBacktickString, InBlockComment, InComment, InLineComment, InNestedComment,
+    InPreservedComment, InSingleQuoteString, InString, LineCommentContinuation, MaybeCommentEnd,
+    MaybeLineSeparator, MaybeNestedCommentEnd, MaybeNestedCommentStart, MaybePreservedComment,
+    MaybePreservedCommentEnd, SingleQuoteEscape, StringEscape, Top,
 };
 
+/// How deep [`CommentSettings::nested_block_comments`] may nest before it is an error.
+pub const MAX_COMMENT_NESTING: u16 = 256;
+
 /// A [`Read`] that transforms another [`Read`] so that it changes all comments to spaces so that a downstream json parser
 /// (such as json-serde) doesn't choke on them.
 ///
@@ -50,6 +162,11 @@ use State::{
 ///   - C style line comments (`// ...`)
 ///   - Shell style line comments (`# ...`)
 ///
+/// Options such as [`with_cancel`](Self::with_cancel) and
+/// [`on_value_boundary`](Self::on_value_boundary) apply whatever the [`CommentSettings`]. Add
+/// them before the first read, since the input hash and value boundary offsets only cover input
+/// read afterwards.
+///
 /// ## Example
 /// ```
 /// use json_strip_comments::StripComments;
@@ -65,26 +182,64 @@ use State::{
 /// StripComments::new(input.as_bytes()).read_to_string(&mut stripped).unwrap();
 ///
 /// assert_eq!(stripped, "{
-///                  \n\"a\": \"comment in string /* a */\",
-///                     \n}           ");
+///                  \n\"a\": \"comment in string /* a */\" \n                    \n}           ");
 ///
 /// ```
 ///
+#[cfg(feature = "std")]
 pub struct StripComments<T: Read> {
     inner: T,
     state: State,
     settings: CommentSettings,
+    cancel: Option<Arc<AtomicBool>>,
+    progress: Progress,
+    /// The most input bytes to accept.
+    max_bytes: Option<usize>,
+    /// A hash of the raw input so far, and the hash it must have at the end.
+    #[cfg(feature = "sha2")]
+    integrity: Option<(sha2::Sha256, [u8; 32])>,
+    value_boundaries: Option<ValueBoundaries>,
+    held: Held,
+    /// The error for input that ended inside a string, with
+    /// [`CommentSettings::recover_unterminated_strings`].
+    unterminated_string: Option<StripError>,
 }
 
+#[cfg(feature = "std")]
 impl<T> StripComments<T>
 where
     T: Read,
 {
-    pub fn new(input: T) -> Self {
+    /// Creates a `StripComments` with the default settings.
+    ///
+    /// This is a `const fn`, so a reader that can be created in a constant can be wrapped in a
+    /// `static`.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use std::{io::Read, sync::Mutex};
+    ///
+    /// static CONFIG: Mutex<StripComments<&[u8]>> =
+    ///     Mutex::new(StripComments::new(b"{\"a\": 1} // built in".as_slice()));
+    ///
+    /// let mut out = String::new();
+    /// CONFIG.lock().unwrap().read_to_string(&mut out).unwrap();
+    /// assert_eq!(out, "{\"a\": 1}            ");
+    /// ```
+    pub const fn new(input: T) -> Self {
         Self {
             inner: input,
             state: Top,
-            settings: CommentSettings::default(),
+            settings: CommentSettings::all(),
+            cancel: None,
+            progress: Progress::new(),
+            max_bytes: None,
+            #[cfg(feature = "sha2")]
+            integrity: None,
+            value_boundaries: None,
+            held: Held::new(),
+            unterminated_string: None,
         }
     }
 
@@ -92,491 +247,4994 @@ where
     ///
     /// This is useful if you wish to disable allowing certain kinds of comments.
     #[inline]
-    pub fn with_settings(settings: CommentSettings, input: T) -> Self {
+    pub const fn with_settings(settings: CommentSettings, input: T) -> Self {
         Self {
             inner: input,
             state: Top,
             settings,
+            cancel: None,
+            progress: Progress::new(),
+            max_bytes: None,
+            #[cfg(feature = "sha2")]
+            integrity: None,
+            value_boundaries: None,
+            held: Held::new(),
+            unterminated_string: None,
         }
     }
-}
 
-impl<T> Read for StripComments<T>
-where
-    T: Read,
-{
-    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let count = self.inner.read(buf)?;
-        if count > 0 {
-            strip_buf(&mut self.state, &mut buf[..count], self.settings)?;
-        } else if self.state != Top && self.state != InLineComment {
-            return Err(ErrorKind::InvalidData.into());
-        }
-        Ok(count)
+    /// Makes the reader cancellable from another thread.
+    ///
+    /// The flag is checked once per [`Read::read`] call. Once it is set, reads fail with an
+    /// [`ErrorKind::Other`] error instead of reading more input. `ErrorKind::Interrupted` is
+    /// deliberately not used, because [`Read::read_to_end`] retries interrupted reads forever.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::CommentSettings;
+    /// use std::io::Read;
+    /// use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    ///
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let mut reader = CommentSettings::hash_only()
+    ///     .strip_comments(r#"{"a": 1} # done"#.as_bytes())
+    ///     .with_cancel(cancel.clone());
+    ///
+    /// cancel.store(true, Ordering::Relaxed);
+    /// assert!(reader.read_to_string(&mut String::new()).is_err());
+    /// ```
+    #[inline]
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
     }
-}
 
-/// Strips comments from a string in place, replacing it with whitespaces.
-///
-/// /// ## Example
-/// ```
-/// use json_strip_comments::{strip_comments_in_place, CommentSettings};
-///
-/// let mut string = String::from(r#"{
-/// // c line comment
-/// "a": "comment in string /* a */"
-/// ## shell line comment
-/// } /** end */"#);
-///
-/// strip_comments_in_place(&mut string, CommentSettings::default()).unwrap();
-///
-/// assert_eq!(string, "{
-///                  \n\"a\": \"comment in string /* a */\"
-///                     \n}           ");
-///
-/// ```
-pub fn strip_comments_in_place(s: &mut str, settings: CommentSettings) -> Result<()> {
-    // Safety: we have made sure the text is UTF-8
-    strip_buf(&mut Top, unsafe { s.as_bytes_mut() }, settings)
-}
+    /// Makes the reader fail once the input is longer than `limit` bytes.
+    ///
+    /// This bounds the work done on untrusted streams. No more than `limit + 1` bytes are ever
+    /// read from the input, and the read that goes over the limit fails with an
+    /// [`ErrorKind::InvalidData`] error.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use std::io::Read;
+    ///
+    /// let input = r#"{"a": 1} // small"#;
+    /// let mut out = String::new();
+    /// StripComments::new(input.as_bytes()).with_max_bytes(64).read_to_string(&mut out).unwrap();
+    ///
+    /// let mut reader = StripComments::new(input.as_bytes()).with_max_bytes(8);
+    /// assert!(reader.read_to_string(&mut String::new()).is_err());
+    /// ```
+    #[inline]
+    pub fn with_max_bytes(mut self, limit: usize) -> Self {
+        self.max_bytes = Some(limit);
+        self
+    }
 
-pub fn strip(s: &mut str) -> Result<()> {
-    strip_comments_in_place(s, CommentSettings::all())
-}
+    /// Makes the reader check the SHA-256 hash of the raw input, before stripping, against
+    /// `expected`.
+    ///
+    /// The input is hashed as it is read, so the check costs no extra pass. On a mismatch, the
+    /// read that reaches the end of the input fails with an [`ErrorKind::InvalidData`] error, so
+    /// output from a tampered or corrupted input should be discarded unless the reader ran to
+    /// completion.
+    ///
+    /// Enabled with the `sha2` feature.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use sha2::{Digest, Sha256};
+    /// use std::io::Read;
+    ///
+    /// let input = r#"{"a": 1} // signed"#;
+    /// let expected = Sha256::digest(input).into();
+    ///
+    /// let mut stripped = String::new();
+    /// StripComments::new(input.as_bytes())
+    ///     .with_integrity(expected)
+    ///     .read_to_string(&mut stripped)
+    ///     .unwrap();
+    ///
+    /// let tampered = r#"{"a": 2} // signed"#;
+    /// let mut reader = StripComments::new(tampered.as_bytes()).with_integrity(expected);
+    /// assert!(reader.read_to_string(&mut String::new()).is_err());
+    /// ```
+    #[cfg(feature = "sha2")]
+    #[inline]
+    pub fn with_integrity(mut self, expected: [u8; 32]) -> Self {
+        use sha2::Digest;
 
-/// Settings for `StripComments`
-///
-/// The default is for all comment types to be enabled.
-#[derive(Copy, Clone, Debug)]
-pub struct CommentSettings {
-    /// True if c-style block comments (`/* ... */`) are removed.
-    pub block_comments: bool,
-    /// True if c-style `//` line comments are removed.
-    pub slash_line_comments: bool,
-    /// True if shell-style `#` line comments are removed.
-    pub hash_line_comments: bool,
-    /// True of trailing commas are removed.
-    pub trailing_commas: bool,
-}
+        self.integrity = Some((sha2::Sha256::new(), expected));
+        self
+    }
 
-impl Default for CommentSettings {
-    fn default() -> Self {
-        Self::all()
+    /// Makes the reader call `f` with the offset just past each complete top-level value, such
+    /// as each object in a stream of concatenated JSON.
+    ///
+    /// A value is complete when its closing bracket or quote is read, or, for a top-level
+    /// number or keyword, when the byte after it is read or the input ends. Offsets are into the
+    /// input, which is the same as the output, so a consumer can split the stripped stream
+    /// there without scanning it again. Each offset is reported during the read that strips it.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use std::{io::Read, sync::mpsc};
+    ///
+    /// let (send, recv) = mpsc::channel();
+    /// let input = "{\"a\": [1]} // one\n[2] 3 /* three */ \"4\"";
+    /// let mut reader = StripComments::new(input.as_bytes())
+    ///     .on_value_boundary(move |offset| send.send(offset).unwrap());
+    /// reader.read_to_string(&mut String::new()).unwrap();
+    ///
+    /// assert_eq!(recv.try_iter().collect::<Vec<_>>(), [10, 21, 23, 39]);
+    /// ```
+    pub fn on_value_boundary(mut self, f: impl FnMut(usize) + Send + 'static) -> Self {
+        self.value_boundaries = Some(ValueBoundaries::new(Box::new(f)));
+        self
     }
 }
 
-impl CommentSettings {
-    /// Enable all comment Styles
-    pub const fn all() -> Self {
-        Self {
-            block_comments: true,
-            slash_line_comments: true,
-            hash_line_comments: true,
-            trailing_commas: true,
-        }
-    }
-    /// Only allow line comments starting with `#`
-    pub const fn hash_only() -> Self {
-        Self {
-            hash_line_comments: true,
-            block_comments: false,
-            slash_line_comments: false,
-            trailing_commas: false,
-        }
+#[cfg(feature = "std")]
+impl<T: Read> StripComments<T> {
+    /// Returns what has been removed from the input read so far.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use std::io::Read;
+    ///
+    /// let mut reader = StripComments::new(r#"{"a": [1,]} // x"#.as_bytes());
+    /// reader.read_to_string(&mut String::new()).unwrap();
+    ///
+    /// let stats = reader.stats();
+    /// assert_eq!((stats.comments, stats.commas), (1, 1));
+    /// assert!(stats.modified());
+    /// ```
+    pub fn stats(&self) -> StripStats {
+        self.progress.stats
     }
-    /// Only allow "c-style" comments.
+
+    /// The error for input that ended inside a string, if it did and
+    /// [`CommentSettings::recover_unterminated_strings`] is set. Its offset is where the string
+    /// began.
     ///
-    /// Specifically, line comments beginning with `//` and
-    /// block comment like `/* ... */`.
-    pub const fn c_style() -> Self {
-        Self {
-            block_comments: true,
-            slash_line_comments: true,
-            hash_line_comments: false,
-            trailing_commas: true,
-        }
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::{CommentSettings, StripComments, StripErrorKind};
+    /// use std::io::Read;
+    ///
+    /// let mut settings = CommentSettings::all();
+    /// settings.recover_unterminated_strings = true;
+    /// let mut reader = StripComments::with_settings(settings, "[1, /* a */ \"b".as_bytes());
+    /// let mut out = String::new();
+    /// reader.read_to_string(&mut out).unwrap();
+    /// assert_eq!(out, "[1,         \"b");
+    ///
+    /// let err = reader.unterminated_string().unwrap();
+    /// assert_eq!((err.kind(), err.offset()), (StripErrorKind::UnterminatedString, 12));
+    /// ```
+    pub fn unterminated_string(&self) -> Option<StripError> {
+        self.unterminated_string
     }
 
-    /// Create a new `StripComments` for `input`, using these settings.
+    /// Whether the input read so far ends outside of any string or comment, so that the output
+    /// so far is a safe place to stop and hand over to a parser.
     ///
-    /// Transform `input` into a [`Read`] that strips out comments.
-    /// The types of comments to support are determined by the configuration of
-    /// `self`.
+    /// A comma that may be trailing is held back until what follows it is read, so the output
+    /// may lag behind the input even at the top level.
     ///
-    /// ## Examples
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
+    /// use std::io::Read;
     ///
+    /// let mut reader = StripComments::new("[1] /* a */ [\"b".as_bytes());
+    /// let mut buf = [0; 4];
+    /// reader.read_exact(&mut buf).unwrap();
+    /// assert!(reader.is_at_top_level());
+    /// reader.read_to_end(&mut Vec::new()).unwrap_err();
+    /// assert!(!reader.is_at_top_level());
     /// ```
-    /// use json_strip_comments::CommentSettings;
+    pub fn is_at_top_level(&self) -> bool {
+        self.state == Top
+    }
+
+    /// Whether the input read so far could end here without an error: it is at the top level
+    /// or inside a line comment, which the end of the input closes.
+    pub fn is_complete(&self) -> bool {
+        self.state.is_complete(self.settings)
+    }
+
+    /// Starts stripping afresh, as if the next byte read began a new document.
+    ///
+    /// The inner reader is left alone, as are [`stats`](Self::stats) and the offsets reported in
+    /// errors, which keep counting from the start of the whole input. Calling this inside a
+    /// comment or string discards that state, so the rest of it is read as JSON; a comma held
+    /// back to see whether it is trailing is discarded too.
+    ///
+    /// ## Example
+    /// ```
+    /// use json_strip_comments::StripComments;
     /// use std::io::Read;
     ///
-    /// let input = r#"{
-    /// // c line comment
-    /// "a": "b"
-    /// /** multi line
-    /// comment
-    /// */ }"#;
+    /// let mut reader = StripComments::new("[1, /* unterminated".as_bytes());
+    /// let mut doc = [0; 6];
+    /// reader.read_exact(&mut doc).unwrap();
+    /// assert_eq!(&doc, b"[1,   ");
     ///
-    /// let mut stripped = String::new();
-    /// CommentSettings::c_style().strip_comments(input.as_bytes()).read_to_string(&mut stripped).unwrap();
+    /// reader.reset();
+    /// *reader.get_mut() = "[2] // two".as_bytes();
+    /// let mut next = String::new();
+    /// reader.read_to_string(&mut next).unwrap();
+    /// assert_eq!(next, "[2]       ");
+    /// ```
+    pub fn reset(&mut self) {
+        self.state = Top;
+        self.held = Held::new();
+        self.unterminated_string = None;
+        if let Some(boundaries) = &mut self.value_boundaries {
+            boundaries.reset();
+        }
+    }
+
+    /// Returns an iterator over the lines of the stripped output, as for newline-delimited
+    /// documents with comments.
     ///
-    /// assert_eq!(stripped, "{
-    ///                  \n\"a\": \"b\"
-    ///                           }");
+    /// A line that held nothing but a comment comes out as whitespace, so blank lines can be
+    /// skipped with `filter(|line| !line.trim().is_empty())`. A block comment spanning several
+    /// lines blanks the line breaks inside it, joining the lines around it into one.
+    ///
+    /// ## Example
     /// ```
+    /// use json_strip_comments::StripComments;
     ///
+    /// let input = "# events\n{\"a\": 1} // first\n\n{\"b\": [2,]}\n";
+    /// let lines: Vec<String> = StripComments::new(input.as_bytes())
+    ///     .stripped_lines()
+    ///     .map(Result::unwrap)
+    ///     .filter(|line| !line.trim().is_empty())
+    ///     .collect();
+    /// assert_eq!(lines, ["{\"a\": 1}         ", "{\"b\": [2 ]}"]);
     /// ```
-    /// use json_strip_comments::CommentSettings;
-    /// use std::io::Read;
+    pub fn stripped_lines(self) -> Lines<Self> {
+        self.lines()
+    }
+
+    /// Gets a reference to the underlying reader.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying reader.
     ///
-    /// let input = r#"{
-    /// ## shell line comment
-    /// "a": "b"
-    /// }"#;
+    /// Reading from it directly skips stripping and leaves this reader's state as it was.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Unwraps this `StripComments`, returning the underlying reader.
     ///
-    /// let mut stripped = String::new();
-    /// CommentSettings::hash_only().strip_comments(input.as_bytes()).read_to_string(&mut stripped).unwrap();
+    /// A comma held back until the next token shows whether it is trailing is lost, along with
+    /// any stripped bytes not yet returned by [`Read::read`].
     ///
-    /// assert_eq!(stripped, "{
-    ///                     \n\"a\": \"b\"\n}");
+    /// ## Example
     /// ```
-    #[inline]
-    pub fn strip_comments<I: Read>(self, input: I) -> StripComments<I> {
-        StripComments::with_settings(self, input)
+    /// use json_strip_comments::StripComments;
+    /// use std::io::Read;
+    ///
+    /// let mut reader = StripComments::new("{} // header\n".as_bytes());
+    /// let mut header = [0; 13];
+    /// reader.read_exact(&mut header).unwrap();
+    ///
+    /// let mut rest = String::new();
+    /// reader.into_inner().read_to_string(&mut rest).unwrap();
+    /// assert_eq!(rest, "");
+    /// ```
+    pub fn into_inner(self) -> T {
+        self.inner
     }
 }
 
-fn consume_comment_whitespace_until_maybe_bracket(
-    state: &mut State,
-    buf: &mut [u8],
-    i: &mut usize,
+/// Configures a [`StripComments`] one setting at a time.
+///
+/// Settings not mentioned keep their [`CommentSettings::default`] value, so code using the
+/// builder keeps compiling as settings are added.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::StripCommentsBuilder;
+/// use std::io::Read;
+///
+/// let mut reader = StripCommentsBuilder::new()
+///     .hash_line_comments(false)
+///     .trailing_commas(false)
+///     .fill(b'.')
+///     .build("[1, /* one */] # keep".as_bytes());
+///
+/// let mut out = String::new();
+/// reader.read_to_string(&mut out).unwrap();
+/// assert_eq!(out, "[1, .........] # keep");
+/// ```
+#[cfg(feature = "std")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StripCommentsBuilder {
     settings: CommentSettings,
-) -> Result<bool> {
-    *i += 1;
-    while *i < buf.len() {
-        let c = &mut buf[*i];
-        *state = match state {
-            Top => {
-                *state = top(c, settings);
-                if c.is_ascii_whitespace() {
-                    *i += 1;
-                    continue;
-                }
-                return Ok(*c == b'}' || *c == b']');
-            }
-            InString => in_string(*c),
-            StringEscape => InString,
-            InComment => in_comment(c, settings)?,
-            InBlockComment => consume_block_comments(buf, i),
-            MaybeCommentEnd => maybe_comment_end(c),
-            InLineComment => consume_line_comments(buf, i),
-        };
-        *i += 1;
-    }
-    Ok(false)
 }
 
-fn strip_buf(state: &mut State, buf: &mut [u8], settings: CommentSettings) -> Result<()> {
-    let mut i = 0;
-    let len = buf.len();
-    while i < len {
-        let c = &mut buf[i];
-        if matches!(state, Top) {
-            let cur = i;
-            *state = top(c, settings);
-            if settings.trailing_commas
-                && *c == b','
-                && consume_comment_whitespace_until_maybe_bracket(state, buf, &mut i, settings)?
-            {
-                buf[cur] = b' ';
-            }
-        } else {
-            *state = match state {
-                Top => unreachable!(),
-                InString => in_string(*c),
-                StringEscape => InString,
-                InComment => in_comment(c, settings)?,
-                InBlockComment => consume_block_comments(buf, &mut i),
-                MaybeCommentEnd => maybe_comment_end(c),
-                InLineComment => consume_line_comments(buf, &mut i),
-            }
-        }
-        i += 1;
+#[cfg(feature = "std")]
+impl StripCommentsBuilder {
+    /// Starts from the default settings, which strip everything.
+    pub fn new() -> Self {
+        Self::default()
     }
-    Ok(())
-}
 
-#[inline]
-fn consume_line_comments(buf: &mut [u8], i: &mut usize) -> State {
-    let cur = *i;
-    match memchr::memchr(b'\n', &buf[*i..]) {
-        Some(offset) => {
-            *i += offset;
-            buf[cur..*i].fill(b' ');
-            Top
-        }
-        None => {
-            *i = buf.len() - 1;
-            buf[cur..].fill(b' ');
-            InLineComment
-        }
+    /// Replaces all settings at once.
+    pub fn settings(mut self, settings: CommentSettings) -> Self {
+        self.settings = settings;
+        self
     }
-}
 
-#[inline]
-fn consume_block_comments(buf: &mut [u8], i: &mut usize) -> State {
-    let cur = *i;
-    match memchr::memchr(b'*', &buf[*i..]) {
-        Some(offset) => {
-            *i += offset;
-            buf[cur..=*i].fill(b' ');
-            MaybeCommentEnd
-        }
-        None => {
-            *i = buf.len() - 1;
-            buf[cur..].fill(b' ');
-            InBlockComment
-        }
+    /// Sets [`CommentSettings::block_comments`].
+    pub fn block_comments(mut self, enabled: bool) -> Self {
+        self.settings.block_comments = enabled;
+        self
     }
-}
 
-#[inline]
-fn top(c: &mut u8, settings: CommentSettings) -> State {
-    match *c {
-        b'"' => InString,
-        b'/' => {
-            if settings.block_comments || settings.slash_line_comments {
-                *c = b' ';
-            }
-            InComment
-        }
-        b'#' if settings.hash_line_comments => {
-            *c = b' ';
-            InLineComment
-        }
-        _ => Top,
+    /// Sets [`CommentSettings::slash_line_comments`].
+    pub fn slash_line_comments(mut self, enabled: bool) -> Self {
+        self.settings.slash_line_comments = enabled;
+        self
     }
-}
 
-#[inline]
-fn in_string(c: u8) -> State {
+    /// Sets [`CommentSettings::hash_line_comments`].
+    pub fn hash_line_comments(mut self, enabled: bool) -> Self {
+        self.settings.hash_line_comments = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::trailing_commas`].
+    pub fn trailing_commas(mut self, enabled: bool) -> Self {
+        self.settings.trailing_commas = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::elide_empty_elements`].
+    pub fn elide_empty_elements(mut self, enabled: bool) -> Self {
+        self.settings.elide_empty_elements = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::line_comment_continuation`].
+    pub fn line_comment_continuation(mut self, enabled: bool) -> Self {
+        self.settings.line_comment_continuation = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::nested_block_comments`].
+    pub fn nested_block_comments(mut self, enabled: bool) -> Self {
+        self.settings.nested_block_comments = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::unicode_line_separators`].
+    pub fn unicode_line_separators(mut self, enabled: bool) -> Self {
+        self.settings.unicode_line_separators = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::lone_slashes`].
+    pub fn lone_slashes(mut self, enabled: bool) -> Self {
+        self.settings.lone_slashes = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::forbid_control_chars_in_strings`].
+    pub fn forbid_control_chars_in_strings(mut self, enabled: bool) -> Self {
+        self.settings.forbid_control_chars_in_strings = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::recover_unterminated_strings`].
+    pub fn recover_unterminated_strings(mut self, enabled: bool) -> Self {
+        self.settings.recover_unterminated_strings = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::backtick_strings`].
+    pub fn backtick_strings(mut self, enabled: bool) -> Self {
+        self.settings.backtick_strings = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::single_quote_strings`].
+    pub fn single_quote_strings(mut self, enabled: bool) -> Self {
+        self.settings.single_quote_strings = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::string_delimiter`].
+    pub fn string_delimiter(mut self, delimiter: u8) -> Self {
+        self.settings.string_delimiter = delimiter;
+        self
+    }
+
+    /// Sets [`CommentSettings::skip_bom`].
+    pub fn skip_bom(mut self, enabled: bool) -> Self {
+        self.settings.skip_bom = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::strip_leading_shebang`].
+    pub fn strip_leading_shebang(mut self, enabled: bool) -> Self {
+        self.settings.strip_leading_shebang = enabled;
+        self
+    }
+
+    /// Sets [`CommentSettings::preserve_comment_prefix`].
+    pub fn preserve_comment_prefix(mut self, prefix: Option<CommentPrefix>) -> Self {
+        self.settings.preserve_comment_prefix = prefix;
+        self
+    }
+
+    /// Sets [`CommentSettings::scan_strategy`].
+    pub fn scan_strategy(mut self, strategy: ScanStrategy) -> Self {
+        self.settings.scan_strategy = strategy;
+        self
+    }
+
+    /// Sets [`CommentSettings::fill`].
+    pub fn fill(mut self, fill: u8) -> Self {
+        self.settings.fill = fill;
+        self
+    }
+
+    /// Creates a [`StripComments`] reading from `input` with these settings.
+    pub fn build<T: Read>(self, input: T) -> StripComments<T> {
+        StripComments::with_settings(self.settings, input)
+    }
+}
+
+/// What [`StripComments`] tracks across reads, besides the state.
+#[cfg(feature = "std")]
+struct Progress {
+    stats: StripStats,
+    /// Bytes stripped by previous reads.
+    consumed: usize,
+    /// The offset in the whole input where the last string or comment began.
+    start: usize,
+    /// How many bytes of the opening of a kept comment came before the input being stripped,
+    /// still blanked in what is held back.
+    opening_before_input: usize,
+}
+
+#[cfg(feature = "std")]
+impl Observer for Progress {
+    fn trailing_comma(&mut self, _pos: usize) {
+        self.stats.commas += 1;
+        self.stats.bytes_blanked += 1;
+    }
+
+    fn comment_start(&mut self, pos: usize) {
+        self.stats.comments += 1;
+        self.start = self.consumed + pos;
+    }
+
+    fn comment_kind(&mut self, kind: CommentKind) {
+        match kind {
+            CommentKind::Block => self.stats.block_comments += 1,
+            CommentKind::Slash => self.stats.line_comments += 1,
+            CommentKind::Hash => self.stats.hash_comments += 1,
+        }
+    }
+
+    fn comment_end(&mut self, pos: usize) {
+        self.stats.bytes_blanked += self.consumed + pos - self.start;
+    }
+
+    fn string_start(&mut self, pos: usize) {
+        self.start = self.consumed + pos;
+    }
+
+    fn comment_preserved(&mut self, before_input: usize) {
+        self.stats.comments -= 1;
+        self.stats.block_comments -= 1;
+        self.opening_before_input += before_input;
+    }
+}
+
+#[cfg(feature = "std")]
+impl Progress {
+    const fn new() -> Self {
+        Self {
+            stats: StripStats {
+                comments: 0,
+                block_comments: 0,
+                line_comments: 0,
+                hash_comments: 0,
+                commas: 0,
+                bytes_blanked: 0,
+            },
+            consumed: 0,
+            start: 0,
+            opening_before_input: 0,
+        }
+    }
+
+    /// Reports a comment whose `/`, kept with [`CommentSettings::lone_slashes`], ended the
+    /// previous input.
+    fn comment_start_before_input(&mut self) {
+        self.stats.comments += 1;
+        self.start = self.consumed - 1;
+    }
+
+    /// Counts a line comment that runs to the end of the input, which ended in `state`, up to
+    /// `consumed`.
+    fn finish(&mut self, state: State) {
+        if matches!(
+            state,
+            InLineComment | LineCommentContinuation | MaybeLineSeparator(_)
+        ) {
+            self.stats.bytes_blanked += self.consumed - self.start;
+            self.start = self.consumed;
+        }
+    }
+}
+
+/// Finds where top-level values end in the input, for [`StripComments::on_value_boundary`].
+///
+/// It runs the state machine over the input as it is read, so strings and comments, kept ones
+/// included, are told apart exactly as stripping tells them, whatever the fill byte.
+#[cfg(feature = "std")]
+struct ValueBoundaries {
+    callback: Box<dyn FnMut(usize) + Send>,
+    /// Bytes scanned so far.
+    offset: usize,
+    state: State,
+    depth: usize,
+    /// True inside a top-level number or keyword.
+    in_scalar: bool,
+}
+
+#[cfg(feature = "std")]
+impl ValueBoundaries {
+    fn new(callback: Box<dyn FnMut(usize) + Send>) -> Self {
+        Self {
+            callback,
+            offset: 0,
+            state: Top,
+            depth: 0,
+            in_scalar: false,
+        }
+    }
+
+    /// Forgets any value in progress, keeping the offset.
+    fn reset(&mut self) {
+        self.state = Top;
+        self.depth = 0;
+        self.in_scalar = false;
+    }
+
+    /// Scans the next input, before it is stripped with `settings`.
+    fn scan(&mut self, input: &[u8], settings: CommentSettings) {
+        let base = self.offset;
+        self.offset += input.len();
+        for (i, &c) in input.iter().enumerate() {
+            let Ok((next, _)) = transition(self.state, c, settings) else {
+                // Stripping fails here as well, so nothing after it is handed out.
+                return;
+            };
+            let pos = base + i;
+            match (std::mem::replace(&mut self.state, next), next) {
+                (Top, Top) => self.structure(c, pos),
+                // A `/` may turn out to be an ordinary byte with `lone_slashes`.
+                (Top, InComment) => {}
+                (Top, _) => self.end_scalar(pos),
+                (InComment, Top) => self.structure(c, pos),
+                (InComment, _) => self.end_scalar(pos - 1),
+                (InString | InSingleQuoteString | InBacktickString, Top) if self.depth == 0 => {
+                    (self.callback)(pos + 1);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Handles a byte outside strings and comments.
+    fn structure(&mut self, c: u8, pos: usize) {
+        let delimiter =
+            c.is_ascii_whitespace() || matches!(c, b'{' | b'}' | b'[' | b']' | b',' | b':');
+        if delimiter {
+            self.end_scalar(pos);
+        }
+        match c {
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' if self.depth > 0 => {
+                self.depth -= 1;
+                if self.depth == 0 {
+                    (self.callback)(pos + 1);
+                }
+            }
+            _ if self.depth == 0 && !delimiter => self.in_scalar = true,
+            _ => {}
+        }
+    }
+
+    /// Reports the end of a top-level number or keyword, if one is in progress.
+    fn end_scalar(&mut self, pos: usize) {
+        if self.in_scalar {
+            self.in_scalar = false;
+            (self.callback)(pos);
+        }
+    }
+
+    /// Reports a top-level number or keyword that runs to the end of the input.
+    fn finish(&mut self) {
+        self.end_scalar(self.offset);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Read for StripComments<T>
+where
+    T: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.check_cancelled()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let count = match self.held.serve(buf) {
+                0 => self.read_input(buf)?,
+                served => served,
+            };
+            if count > 0 {
+                return Ok(count);
+            }
+            if self.held.is_empty() {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Stripped output is buffered by the reader itself, so the inner reader need not implement
+/// `BufRead` for [`BufRead::lines`] and the like.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::StripComments;
+/// use std::io::BufRead;
+///
+/// let input = "{\"a\": 1} // one\n{\"b\": [2,]} # two\n";
+/// let lines: Vec<String> = StripComments::new(input.as_bytes())
+///     .lines()
+///     .map(|line| line.unwrap().trim_end().to_owned())
+///     .collect();
+/// assert_eq!(lines, ["{\"a\": 1}", "{\"b\": [2 ]}"]);
+/// ```
+#[cfg(feature = "std")]
+impl<T: Read> BufRead for StripComments<T> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        self.check_cancelled()?;
+        while self.held.ready().is_empty() {
+            let mut buf = [0; 8192];
+            let count = self.read_input(&mut buf)?;
+            if count == 0 && self.held.is_empty() {
+                break;
+            }
+            // Stripped output comes before anything that was held back while stripping it.
+            self.held.unshift(&buf[..count]);
+        }
+        Ok(self.held.ready())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        let amt = amt.min(self.held.ready().len());
+        self.held.consume(amt);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read> StripComments<T> {
+    fn check_cancelled(&self) -> Result<()> {
+        if self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(Error::other("stripping was cancelled"));
+        }
+        Ok(())
+    }
+
+    /// Reads and strips the next input into `buf`, returning how many bytes of it are ready to
+    /// hand out. Anything else read is moved to `held`, so `0` only means the end of the input
+    /// if nothing is held.
+    fn read_input(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if (self.settings.skip_bom || self.settings.strip_leading_shebang)
+            && self.progress.consumed == 0
+            && buf.len() < BOM.len()
+        {
+            // Too small to hold a whole leading mark or shebang, so look for it in a buffer that
+            // is not, and hand out what was read from there.
+            let mut head = [0; BOM.len()];
+            let count = self.read_input(&mut head)?;
+            self.held.unshift(&head[..count]);
+            return Ok(0);
+        }
+        let mut buf = buf;
+        if let Some(limit) = self.max_bytes {
+            // Read one byte past the limit, so that going over it is noticed.
+            let allowed = (limit - self.progress.consumed).saturating_add(1);
+            let len = buf.len().min(allowed);
+            buf = &mut buf[..len];
+        }
+        let mut count = self.inner.read(buf)?;
+        if self.progress.consumed == 0 {
+            // A leading mark or shebang may be split over several reads of the inner reader, so
+            // make sure all of it is in this buffer before looking for it.
+            while count > 0 && count < buf.len() && partial_start(&buf[..count], self.settings) {
+                match self.inner.read(&mut buf[count..])? {
+                    0 => break,
+                    n => count += n,
+                }
+            }
+        }
+        if self
+            .max_bytes
+            .is_some_and(|limit| self.progress.consumed + count > limit)
+        {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "input is longer than the maximum length",
+            ));
+        }
+        if count > 0 {
+            #[cfg(feature = "sha2")]
+            if let Some((hasher, _)) = &mut self.integrity {
+                sha2::Digest::update(hasher, &buf[..count]);
+            }
+            if self.progress.consumed == 0 {
+                self.state = start_input(&mut buf[..count], self.settings, &mut self.progress);
+            }
+            if let Some(boundaries) = &mut self.value_boundaries {
+                if self.progress.consumed == 0 {
+                    boundaries.state = self.state;
+                }
+                boundaries.scan(&buf[..count], self.settings);
+            }
+            let result = self.strip_input(&mut buf[..count]);
+            let base = self.progress.consumed;
+            self.progress.consumed += count;
+            return result.map_err(|err| error::offset_by(err, base));
+        }
+        if self.held.comma.take().is_some() {
+            // Nothing follows the held comma, so it is not trailing and can be handed out.
+            return Ok(0);
+        }
+        if !self.is_complete() {
+            let err = StripError::at_eof(self.state, self.progress.start, self.progress.consumed);
+            if !(self.settings.recover_unterminated_strings
+                && err.kind() == StripErrorKind::UnterminatedString)
+            {
+                return Err(err.into());
+            }
+            self.unterminated_string = Some(err);
+        }
+        self.progress.finish(self.state);
+        if let Some(boundaries) = &mut self.value_boundaries {
+            boundaries.finish();
+        }
+        #[cfg(feature = "sha2")]
+        if let Some((hasher, expected)) = &self.integrity {
+            if sha2::Digest::finalize(hasher.clone()).as_slice() != expected {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "input does not match the expected hash",
+                ));
+            }
+        }
+        Ok(0)
+    }
+
+    /// Strips `input`, which follows everything read before, returning how many of its
+    /// leading bytes can be handed out as they are.
+    ///
+    /// A comma that might be trailing can't be handed out until the next bracket or value
+    /// shows whether it is, so it and everything after it are held back until then. So is the
+    /// opening of a block comment until it shows whether it is kept.
+    fn strip_input(&mut self, input: &mut [u8]) -> Result<usize> {
+        let held = self.held.bytes.len();
+        let opening = pending_opening(self.state, self.settings);
+        if opening > 0 && self.held.comma == Some(held - opening) {
+            // Held back for the opening of a comment, not for a comma.
+            self.held.comma = None;
+        }
+        let result = self.strip_held_and_input(input);
+        let before = std::mem::take(&mut self.progress.opening_before_input);
+        if before > 0 {
+            // The kept comment began in what is held back.
+            let prefix = self.settings.preserved_prefix();
+            restore_opening(&mut self.held.bytes[held - before..held], prefix, 0);
+        }
+        result
+    }
+
+    /// [`strip_input`](Self::strip_input), leaving the opening of a kept comment that began
+    /// before `input` blanked.
+    fn strip_held_and_input(&mut self, input: &mut [u8]) -> Result<usize> {
+        if self.state == InComment && self.settings.lone_slashes {
+            // The last input ended with a `/` that was kept and held back in case it did not
+            // start a comment.
+            let slash = self.held.bytes.len() - 1;
+            if !ends_lone_slash(self.state, input[0], self.settings) {
+                self.held.bytes[slash] = self.settings.fill;
+                self.progress.comment_start_before_input();
+            }
+            if self.held.comma == Some(slash) {
+                self.held.comma = None;
+            }
+        }
+        let mut start = 0;
+        if let Some(comma) = self.held.comma {
+            let mut i = 0;
+            match lookahead(
+                &mut self.state,
+                input,
+                &mut i,
+                self.settings,
+                &mut self.progress,
+            )? {
+                Lookahead::OutOfInput => {
+                    self.held.bytes.extend_from_slice(input);
+                    return Ok(0);
+                }
+                Lookahead::Trailing
+                    if self.settings.trailing_commas && self.held.bytes[comma] == b',' =>
+                {
+                    self.held.bytes[comma] = self.settings.fill;
+                    self.progress.trailing_comma(comma);
+                }
+                Lookahead::Trailing | Lookahead::NotTrailing => {}
+            }
+            self.held.comma = None;
+            // A comma or bracket that came next needs its own look ahead, so strip it again.
+            start = if needs_lookahead(input[i], self.settings) {
+                i
+            } else {
+                i + 1
+            };
+        }
+        let comma = strip_chunk(
+            &mut self.state,
+            input,
+            start,
+            self.settings,
+            &mut self.progress,
+        )?;
+        let offset = self.held.bytes.len();
+        let opening = match pending_opening(self.state, self.settings) {
+            0 => None,
+            // It may have begun in what is held back.
+            len => Some(offset + input.len() - len),
+        };
+        if self.held.is_empty() {
+            let hold = comma.into_iter().chain(opening).min();
+            if let Some(hold) = hold {
+                self.held.bytes.extend_from_slice(&input[hold..]);
+                self.held.comma = Some(0);
+            }
+            return Ok(hold.unwrap_or(input.len()));
+        }
+        self.held.bytes.extend_from_slice(input);
+        self.held.comma = comma
+            .map(|comma| offset + comma)
+            .into_iter()
+            .chain(opening)
+            .min();
+        Ok(0)
+    }
+}
+
+/// Stripped output that [`StripComments`] has not handed out yet.
+#[cfg(feature = "std")]
+struct Held {
+    bytes: Vec<u8>,
+    /// How much of `bytes` has been handed out.
+    pos: usize,
+    /// The position in `bytes` of a comma that may still turn out to be trailing, of a comma or
+    /// bracket that may be followed by commas to elide, or of a `/` that may start a comment.
+    /// Nothing from it on can be handed out yet.
+    comma: Option<usize>,
+}
+
+#[cfg(feature = "std")]
+impl Held {
+    const fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            pos: 0,
+            comma: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    /// What can be handed out now.
+    fn ready(&self) -> &[u8] {
+        &self.bytes[self.pos..self.comma.unwrap_or(self.bytes.len())]
+    }
+
+    /// Marks the first `count` bytes of [`ready`](Self::ready) as handed out.
+    fn consume(&mut self, count: usize) {
+        self.pos += count;
+        if self.pos == self.comma.unwrap_or(self.bytes.len()) {
+            // Drop what has been handed out, so that a long run of held commas doesn't grow
+            // the buffer.
+            self.bytes.drain(..self.pos);
+            self.comma = self.comma.map(|comma| comma - self.pos);
+            self.pos = 0;
+        }
+    }
+
+    /// Puts `bytes`, which come before everything held, first in line to be handed out.
+    fn unshift(&mut self, bytes: &[u8]) {
+        self.bytes.splice(self.pos..self.pos, bytes.iter().copied());
+        if let Some(comma) = &mut self.comma {
+            *comma += bytes.len();
+        }
+    }
+
+    /// Copies as much as is ready into `buf`, returning how much that was.
+    fn serve(&mut self, buf: &mut [u8]) -> usize {
+        let ready = self.ready();
+        let count = ready.len().min(buf.len());
+        buf[..count].copy_from_slice(&ready[..count]);
+        self.consume(count);
+        count
+    }
+}
+
+/// Strips comments from a string in place, replacing it with whitespaces.
+///
+/// Input that ends inside a string or block comment is stripped as far as it goes, without an
+/// error. Use [`strip_comments_in_place_strict`] to reject it, as [`StripComments`] does.
+///
+/// /// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_in_place, CommentSettings};
+///
+/// let mut string = String::from(r#"{
+/// // c line comment
+/// "a": "comment in string /* a */"
+/// ## shell line comment
+/// } /** end */"#);
+///
+/// strip_comments_in_place(&mut string, CommentSettings::default()).unwrap();
+///
+/// assert_eq!(string, "{
+///                  \n\"a\": \"comment in string /* a */\"
+///                     \n}           ");
+///
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_in_place(s: &mut str, settings: CommentSettings) -> Result<()> {
+    check_fill(settings)?;
+    // Safety: we have made sure the text is UTF-8, and `check_fill` that it stays UTF-8
+    let buf = unsafe { s.as_bytes_mut() };
+    let mut state = start_input(buf, settings, &mut ());
+    Ok(strip_buf(&mut state, buf, settings)?)
+}
+
+/// Like [`strip_comments_in_place`], but input that ends inside a string or block comment is an
+/// error, as with [`StripComments`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_in_place_strict, CommentSettings};
+///
+/// let mut s = String::from("[1] // one");
+/// strip_comments_in_place_strict(&mut s, CommentSettings::all()).unwrap();
+/// assert_eq!(s, "[1]       ");
+///
+/// let mut s = String::from("[] /*");
+/// assert!(strip_comments_in_place_strict(&mut s, CommentSettings::all()).is_err());
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_in_place_strict(s: &mut str, settings: CommentSettings) -> Result<()> {
+    check_fill(settings)?;
+    // Safety: we have made sure the text is UTF-8, and `check_fill` that it stays UTF-8
+    let buf = unsafe { s.as_bytes_mut() };
+    Ok(strip_slice_strict(buf, settings)?)
+}
+
+/// Like [`strip_comments_in_place`], but also applies `top_level_commas` to each comma between
+/// top-level values, as in `{"a": 1}, {"b": 2}`.
+///
+/// Telling a top-level comma from one inside a value takes the brackets of the whole text, so
+/// only this function looks for them. Commas inside strings are told apart with the same
+/// settings as comments are. A removed comma is replaced with
+/// [`fill`](CommentSettings::fill). With [`TopLevelCommas::Error`], the error carries a
+/// [`StripError`] with the offset of the first one, and `s` is left unchanged on any error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{
+///     strip_comments_in_place_with_top_level_commas, CommentSettings, TopLevelCommas,
+/// };
+///
+/// let mut s = String::from("{\"a\": [1, 2]}, /* b */ {\"b\": 2},");
+/// let settings = CommentSettings::all();
+/// strip_comments_in_place_with_top_level_commas(&mut s, settings, TopLevelCommas::Strip).unwrap();
+/// assert_eq!(s, "{\"a\": [1, 2]}          {\"b\": 2} ");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_in_place_with_top_level_commas(
+    s: &mut str,
+    settings: CommentSettings,
+    top_level_commas: TopLevelCommas,
+) -> Result<()> {
+    if top_level_commas != TopLevelCommas::Error {
+        strip_comments_in_place(s, settings)?;
+        structure::handle_top_level_commas(s, top_level_commas, settings)?;
+        return Ok(());
+    }
+    // Check a stripped copy, so that `s` is only changed once it is known to be clean.
+    let mut stripped = s.to_owned();
+    strip_comments_in_place(&mut stripped, settings)?;
+    structure::handle_top_level_commas(&mut stripped, top_level_commas, settings)?;
+    // Safety: the copy is UTF-8 and as long as `s`, since stripping only replaces bytes.
+    unsafe { s.as_bytes_mut() }.copy_from_slice(stripped.as_bytes());
+    Ok(())
+}
+
+/// Like [`strip_comments_in_place`], but also returns what was removed.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_in_place_with_stats, CommentSettings};
+///
+/// let mut s = String::from("[1, 2] // x");
+/// let stats = strip_comments_in_place_with_stats(&mut s, CommentSettings::all()).unwrap();
+///
+/// assert_eq!((stats.comments, stats.commas), (1, 0));
+/// assert!(stats.modified());
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_in_place_with_stats(
+    s: &mut str,
+    settings: CommentSettings,
+) -> Result<StripStats> {
+    let mut progress = Progress::new();
+    let state = strip_str_observed(s, settings, &mut progress)?;
+    progress.consumed = s.len();
+    progress.finish(state);
+    Ok(progress.stats)
+}
+
+/// Like [`strip_comments_in_place`], but also returns whether anything was removed, so that
+/// text which was already clean need not be written back.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_in_place_reporting, CommentSettings};
+///
+/// let mut s = String::from("[1, 2,]");
+/// assert!(strip_comments_in_place_reporting(&mut s, CommentSettings::all()).unwrap());
+///
+/// let mut s = String::from("[1, \"// 2\"]");
+/// assert!(!strip_comments_in_place_reporting(&mut s, CommentSettings::all()).unwrap());
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_in_place_reporting(s: &mut str, settings: CommentSettings) -> Result<bool> {
+    strip_comments_in_place_with_stats(s, settings).map(|stats| stats.modified())
+}
+
+/// Like [`strip_comments_in_place`], but instead of stopping at the first problem, strips as
+/// much as it can and returns every problem found, in input order, for showing all of them at
+/// once.
+///
+/// After an invalid comment start the `/` is kept and stripping carries on at the top level.
+/// After a raw control character in a string, or a comment nested too deep, it carries on in
+/// the same string or comment. A string or block comment the input ends inside is reported
+/// too; the rest of the input stays inside it, so an unterminated block comment is blanked to
+/// the end. Only an invalid [`fill`](CommentSettings::fill) is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_collect_diagnostics, CommentSettings, StripErrorKind};
+///
+/// let mut s = String::from("[1, / 2, /x] /* open");
+/// let diagnostics = strip_comments_collect_diagnostics(&mut s, CommentSettings::all()).unwrap();
+///
+/// let found: Vec<_> = diagnostics.iter().map(|d| (d.kind(), d.offset())).collect();
+/// assert_eq!(
+///     found,
+///     [
+///         (StripErrorKind::InvalidCommentStart, 5),
+///         (StripErrorKind::InvalidCommentStart, 10),
+///         (StripErrorKind::UnterminatedBlockComment, 13),
+///     ]
+/// );
+/// assert_eq!(s, "[1, / 2, /x]        ");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_comments_collect_diagnostics(
+    s: &mut str,
+    settings: CommentSettings,
+) -> Result<Vec<StripError>> {
+    check_fill(settings)?;
+    // Safety: we have made sure the text is UTF-8, and `check_fill` that it stays UTF-8. The
+    // only other byte written back is a `/` that was there before.
+    let buf = unsafe { s.as_bytes_mut() };
+    let mut diagnostics = Vec::new();
+    let mut start = LastStart::default();
+    let mut state = start_input(buf, settings, &mut start);
+    let mut pos = 0;
+    while let Err(err) = strip_chunk(&mut state, buf, pos, settings, &mut start) {
+        diagnostics.push(err);
+        pos = err.offset();
+        match (err.kind(), state) {
+            // The `/` that looked like the start of a comment is all there is to it.
+            (StripErrorKind::InvalidCommentStart, _) => {
+                buf[pos - 1] = b'/';
+                state = Top;
+            }
+            (StripErrorKind::CommentNestingTooDeep, MaybeNestedCommentStart(depth)) => {
+                buf[pos] = settings.fill;
+                state = InNestedComment(depth);
+                pos += 1;
+            }
+            // A control character is kept in its string, using up an escape before it.
+            (_, StringEscape) => {
+                state = InString;
+                pos += 1;
+            }
+            (_, SingleQuoteEscape) => {
+                state = InSingleQuoteString;
+                pos += 1;
+            }
+            _ => pos += 1,
+        }
+    }
+    if !state.is_complete(settings) {
+        let err = StripError::at_eof(state, start.0, buf.len());
+        if state == InComment {
+            buf[buf.len() - 1] = b'/';
+        }
+        diagnostics.push(err);
+    }
+    Ok(diagnostics)
+}
+
+/// The syntax of a comment.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommentKind {
+    /// `/* ... */`
+    Block,
+    /// `// ...`
+    Slash,
+    /// `# ...`
+    Hash,
+}
+
+/// Counts of what stripping removed.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct StripStats {
+    /// Comments removed, including a line comment that runs to the end of the input.
+    pub comments: usize,
+    /// `/* ... */` comments removed, also counted in [`comments`](Self::comments).
+    pub block_comments: usize,
+    /// `//` comments removed, also counted in [`comments`](Self::comments).
+    pub line_comments: usize,
+    /// `#` comments removed, including a shebang with
+    /// [`CommentSettings::strip_leading_shebang`], also counted in [`comments`](Self::comments).
+    pub hash_comments: usize,
+    /// Trailing commas removed, and commas removed with
+    /// [`CommentSettings::elide_empty_elements`].
+    pub commas: usize,
+    /// The total length in bytes of the removed comments and commas. Line breaks that
+    /// [`CommentSettings::line_comment_continuation`] keeps inside a comment are counted too.
+    pub bytes_blanked: usize,
+}
+
+impl StripStats {
+    /// True if anything was removed.
+    pub fn modified(&self) -> bool {
+        self.comments > 0 || self.commas > 0
+    }
+}
+
+/// [`strip_comments_in_place`] reporting to an [`Observer`], returning the state at the end.
+#[cfg(feature = "std")]
+fn strip_str_observed(
+    s: &mut str,
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) -> Result<State> {
+    check_fill(settings)?;
+    // Safety: we have made sure the text is UTF-8, and `check_fill` that it stays UTF-8
+    let buf = unsafe { s.as_bytes_mut() };
+    let mut state = start_input(buf, settings, observer);
+    strip_buf_observed(&mut state, buf, settings, observer)?;
+    Ok(state)
+}
+
+/// Errors unless [`CommentSettings::fill`] keeps UTF-8 text valid.
+#[cfg(feature = "std")]
+fn check_fill(settings: CommentSettings) -> Result<()> {
+    if settings.fill.is_ascii() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the fill byte must be ASCII",
+        ))
+    }
+}
+
+const BOM: &[u8] = "\u{FEFF}".as_bytes();
+
+const SHEBANG: &[u8] = b"#!";
+
+/// Handles what may only come at the start of the input, in `buf`, returning the state to strip
+/// it from.
+///
+/// With [`CommentSettings::skip_bom`], a byte order mark is replaced with spaces. A mark is
+/// three spaces wide, like its UTF-8 encoding, so offsets into the rest of the input don't
+/// change. With [`CommentSettings::strip_leading_shebang`], a shebang starts the input inside a
+/// line comment.
+fn start_input(buf: &mut [u8], settings: CommentSettings, observer: &mut impl Observer) -> State {
+    if settings.skip_bom && buf.starts_with(BOM) {
+        buf[..BOM.len()].fill(b' ');
+    }
+    if settings.strip_leading_shebang && buf.starts_with(SHEBANG) {
+        observer.comment_start(0);
+        observer.comment_kind(CommentKind::Hash);
+        return InLineComment;
+    }
+    Top
+}
+
+/// True if `head`, the start of the input, is too short to tell whether [`start_input`] has
+/// anything to do.
+#[cfg(feature = "std")]
+fn partial_start(head: &[u8], settings: CommentSettings) -> bool {
+    let partial = |prefix: &[u8]| head.len() < prefix.len() && prefix.starts_with(head);
+    (settings.skip_bom && partial(BOM)) || (settings.strip_leading_shebang && partial(SHEBANG))
+}
+
+#[cfg(feature = "std")]
+pub fn strip(s: &mut str) -> Result<()> {
+    strip_comments_in_place(s, CommentSettings::all())
+}
+
+/// Copies and strips any string-like input, such as a `&str` or `String`.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_str;
+///
+/// assert_eq!(strip_str("[1, /* two */]").unwrap(), "[1           ]");
+/// assert_eq!(strip_str(String::from("# one\n1")).unwrap(), "     \n1");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_str(input: impl AsRef<str>) -> Result<String> {
+    let mut s = input.as_ref().to_owned();
+    strip(&mut s)?;
+    Ok(s)
+}
+
+/// Strips `input`, borrowing it when there is nothing to strip.
+///
+/// Most config files have no comments or trailing commas, so this first scans `input` without
+/// writing anything, and only copies and strips it once a comment or trailing comma turns up.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_cow;
+/// use std::borrow::Cow;
+///
+/// assert!(matches!(strip_cow(r#"{"a": [1, 2]}"#).unwrap(), Cow::Borrowed(_)));
+/// assert_eq!(strip_cow("[1, 2,] // x").unwrap(), "[1, 2 ]     ");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_cow(input: &str) -> Result<Cow<'_, str>> {
+    if needs_stripping(input.as_bytes()) {
+        strip_str(input).map(Cow::Owned)
+    } else {
+        Ok(Cow::Borrowed(input))
+    }
+}
+
+/// Checks that `s` has no comments or trailing commas, without changing it.
+///
+/// This scans `s` as [`strip`] would, but reports the first comment or trailing comma as an
+/// error instead of blanking it, for rejecting files that should already be plain JSON. Input
+/// that [`strip`] would reject is reported the same way.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{check, StripErrorKind};
+///
+/// assert!(check(r#"{"a": "// not a comment"}"#).is_ok());
+///
+/// let err = check("[1, 2, // two\n]").unwrap_err();
+/// assert_eq!((err.kind(), err.offset()), (StripErrorKind::Comment, 7));
+///
+/// let err = check("[1, 2,\n]").unwrap_err();
+/// assert_eq!((err.kind(), err.offset()), (StripErrorKind::TrailingComma, 5));
+/// ```
+#[cfg(feature = "std")]
+pub fn check(s: &str) -> core::result::Result<(), StripError> {
+    let settings = CommentSettings::all();
+    let mut state = Top;
+    let mut start = 0;
+    let mut comma = None;
+    for (i, &c) in s.as_bytes().iter().enumerate() {
+        if state == Top {
+            match (c, comma) {
+                (b',', _) => {
+                    comma = Some(i);
+                    continue;
+                }
+                (b'}' | b']', Some(comma)) => {
+                    return Err(StripError::new(StripErrorKind::TrailingComma, comma));
+                }
+                _ if c.is_ascii_whitespace() => continue,
+                _ => comma = None,
+            }
+            start = i;
+        }
+        match transition(state, c, settings) {
+            Err(kind) => return Err(StripError::new(kind, i)),
+            // Wait for the byte after a `/` to tell a comment from an invalid one.
+            Ok((next @ InComment, _)) | Ok((next, Keep)) => state = next,
+            Ok((_, Blank)) => return Err(StripError::new(StripErrorKind::Comment, start)),
+        }
+    }
+    match state {
+        Top => Ok(()),
+        _ => Err(StripError::at_eof(state, start, s.len())),
+    }
+}
+
+/// Whether [`strip`] would change `buf`, decided without writing to it.
+#[cfg(feature = "std")]
+fn needs_stripping(buf: &[u8]) -> bool {
+    if memchr::memchr3(b'/', b'#', b',', buf).is_none() {
+        return false;
+    }
+    let settings = CommentSettings::all();
+    let mut state = Top;
+    let mut after_comma = false;
+    for &c in buf {
+        if state == Top {
+            match c {
+                b',' => {
+                    after_comma = true;
+                    continue;
+                }
+                b'}' | b']' if after_comma => return true,
+                _ if c.is_ascii_whitespace() => continue,
+                _ => after_comma = false,
+            }
+        }
+        match transition(state, c, settings) {
+            // Comments, and invalid input that stripping reports, both need the full strip.
+            Ok((_, Blank)) | Err(_) => return true,
+            Ok((next, Keep)) => state = next,
+        }
+    }
+    false
+}
+
+/// Copies and strips any byte input, such as a `&[u8]` or `Vec<u8>`.
+///
+/// The input is only read, so this also works for memory-mapped files and `&'static [u8]` data
+/// that [`strip_slice`] can't modify. It does not need to be UTF-8. Only ASCII bytes are ever
+/// replaced, so valid UTF-8 stays valid.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_bytes;
+///
+/// assert_eq!(strip_bytes(b"[1, /* two */]").unwrap(), b"[1           ]");
+/// assert_eq!(strip_bytes(vec![b'1', b'#', 0xff]).unwrap(), b"1  ");
+/// ```
+#[cfg(feature = "std")]
+#[doc(alias = "strip_to_vec")]
+pub fn strip_bytes(input: impl AsRef<[u8]>) -> Result<Vec<u8>> {
+    let mut buf = input.as_ref().to_vec();
+    strip_buf(&mut Top, &mut buf, CommentSettings::all())?;
+    Ok(buf)
+}
+
+/// Like [`strip_bytes`], but appends the stripped bytes to `dst`, so that one buffer can be
+/// cleared and reused for many inputs.
+///
+/// Error offsets are relative to `src`, and on an error `dst` is left as it was.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_into;
+///
+/// let mut buf = Vec::with_capacity(64);
+/// for src in [&b"[1, /* two */]"[..], b"{\"a\": 1,} # b"] {
+///     buf.clear();
+///     strip_into(src, &mut buf).unwrap();
+///     assert_eq!(buf.len(), src.len());
+/// }
+/// assert_eq!(buf, b"{\"a\": 1 }    ");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_into(src: &[u8], dst: &mut Vec<u8>) -> Result<()> {
+    let start = dst.len();
+    dst.extend_from_slice(src);
+    if let Err(err) = strip_buf(&mut Top, &mut dst[start..], CommentSettings::all()) {
+        dst.truncate(start);
+        return Err(err.into());
+    }
+    Ok(())
+}
+
+/// Reads all of `reader` through a [`StripComments`] with the default settings, returning the
+/// stripped text.
+///
+/// Input that is not UTF-8, or that ends inside a string or block comment, is an
+/// [`ErrorKind::InvalidData`] error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_reader_to_string;
+///
+/// let input = "{\"a\": 1, /* b */}";
+/// assert_eq!(strip_reader_to_string(input.as_bytes()).unwrap(), "{\"a\": 1         }");
+/// assert!(strip_reader_to_string("[1] /* open".as_bytes()).is_err());
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_reader_to_string<R: Read>(reader: R) -> Result<String> {
+    let mut out = String::new();
+    StripComments::new(reader).read_to_string(&mut out)?;
+    Ok(out)
+}
+
+/// Like [`strip_reader_to_string`], but for input of any bytes.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_reader_to_vec;
+///
+/// assert_eq!(strip_reader_to_vec(&b"[1, 2,] # \xff"[..]).unwrap(), b"[1, 2 ]    ");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_reader_to_vec<R: Read>(reader: R) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    StripComments::new(reader).read_to_end(&mut out)?;
+    Ok(out)
+}
+
+/// Strips `input` into any [`Extend<u8>`] container, such as a `Vec<u8>` or a `VecDeque<u8>`.
+///
+/// The input is stripped a block at a time through a [`StripComments`] reader, so nothing but
+/// the container grows with the input. Like the reader, unterminated strings and comments are an
+/// error, and bytes already added to `out` are left there.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_extend;
+/// use std::collections::VecDeque;
+///
+/// let mut out = VecDeque::new();
+/// strip_extend(b"[1, /* two */]", &mut out).unwrap();
+/// assert!(out.iter().eq(b"[1           ]"));
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_extend<E: Extend<u8>>(input: &[u8], out: &mut E) -> Result<()> {
+    let mut reader = StripComments::new(input);
+    let mut buf = [0; 8192];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(()),
+            count => out.extend(buf[..count].iter().copied()),
+        }
+    }
+}
+
+/// Strips comments and trailing commas from `buf` in place, without assuming any encoding.
+///
+/// This is for legacy files in single-byte encodings such as Latin-1 or Windows-1252, but works
+/// for any ASCII-compatible encoding. Bytes in strings are kept exactly, and every byte of a
+/// comment, ASCII or not, becomes a space, so byte offsets are preserved. It is
+/// [`strip_slice`] with [`CommentSettings::all`], returning an `io::Error`.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_bytes_latin1;
+///
+/// // "café" in Latin-1, followed by a comment containing "é".
+/// let mut buf = b"[\"caf\xe9\", /* \xe9 */]".to_vec();
+/// strip_bytes_latin1(&mut buf).unwrap();
+/// assert_eq!(buf, b"[\"caf\xe9\"         ]");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_bytes_latin1(buf: &mut [u8]) -> Result<()> {
+    Ok(strip_slice(buf, CommentSettings::all())?)
+}
+
+/// Strips `buf` in place, the core of [`strip_comments_in_place`] that works without the `std`
+/// feature.
+///
+/// The error is a bare [`StripError`] rather than an `io::Error`. With an ASCII
+/// [`fill`](CommentSettings::fill), UTF-8 input stays UTF-8.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_slice, CommentSettings, StripErrorKind};
+///
+/// let mut buf = *b"[1, /* two */] # three";
+/// strip_slice(&mut buf, CommentSettings::all()).unwrap();
+/// assert_eq!(&buf, b"[1           ]        ");
+///
+/// let err = strip_slice(&mut [b'[', b'/', b'x', b']'], CommentSettings::all()).unwrap_err();
+/// assert_eq!((err.kind(), err.offset()), (StripErrorKind::InvalidCommentStart, 2));
+/// ```
+pub fn strip_slice(
+    buf: &mut [u8],
+    settings: CommentSettings,
+) -> core::result::Result<(), StripError> {
+    let mut state = start_input(buf, settings, &mut ());
+    strip_buf(&mut state, buf, settings)
+}
+
+/// Like [`strip_slice`], but input that ends inside a string or block comment is an error.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_slice_strict, CommentSettings, StripErrorKind};
+///
+/// let mut buf = *b"[\"a\", /* b";
+/// let err = strip_slice_strict(&mut buf, CommentSettings::all()).unwrap_err();
+/// assert_eq!((err.kind(), err.offset()), (StripErrorKind::UnterminatedBlockComment, 6));
+/// ```
+pub fn strip_slice_strict(
+    buf: &mut [u8],
+    settings: CommentSettings,
+) -> core::result::Result<(), StripError> {
+    let mut start = LastStart::default();
+    let mut state = start_input(buf, settings, &mut start);
+    strip_buf_observed(&mut state, buf, settings, &mut start)?;
+    if state.is_complete(settings) {
+        Ok(())
+    } else {
+        Err(StripError::at_eof(state, start.0, buf.len()))
+    }
+}
+
+/// Where [`strip_slice_stateful`] got to at the end of one slice of the input, to carry on from
+/// in the next.
+///
+/// This is what a [`StripComments`] keeps between reads, so a file can be stripped in slices of
+/// the caller's choosing, such as pages of a memory map.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct StripState {
+    state: State,
+    /// Bytes stripped in previous slices.
+    consumed: usize,
+    /// [`CommentSettings::lone_slashes`] in the last slice, for
+    /// [`is_complete`](Self::is_complete).
+    lone_slashes: bool,
+}
+
+impl StripState {
+    /// The state at the start of the input.
+    pub const fn new() -> Self {
+        Self {
+            state: Top,
+            consumed: 0,
+            lone_slashes: false,
+        }
+    }
+
+    /// Whether the input so far could end here without an error: it is at the top level or
+    /// inside a line comment, which the end of the input closes.
+    pub fn is_complete(&self) -> bool {
+        self.state.is_complete(CommentSettings {
+            lone_slashes: self.lone_slashes,
+            ..CommentSettings::all()
+        })
+    }
+}
+
+impl Default for StripState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Like [`strip_slice`], for one of several consecutive slices of the input, carrying on from
+/// where the previous slice left `state`.
+///
+/// Error offsets count from the start of the whole input. Unlike [`StripComments`], nothing is
+/// held back across slices, so a trailing comma is only removed if the bracket after it is in
+/// the same slice, a leading byte order mark or shebang is only recognized whole in the first
+/// slice, with [`CommentSettings::lone_slashes`] a `/` ending a slice is kept even if the
+/// next slice shows that it started a comment, and with
+/// [`CommentSettings::preserve_comment_prefix`] the part of a kept comment's opening in an
+/// earlier slice stays blanked. Check [`StripState::is_complete`] after the last
+/// slice to reject input that ends inside a string or block comment.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_slice_stateful, CommentSettings, StripState};
+///
+/// let mut state = StripState::new();
+/// let mut first = *b"[1, /* two";
+/// let mut second = *b" */ 2] # end";
+/// strip_slice_stateful(&mut state, &mut first, CommentSettings::all()).unwrap();
+/// assert!(!state.is_complete());
+/// strip_slice_stateful(&mut state, &mut second, CommentSettings::all()).unwrap();
+/// assert!(state.is_complete());
+/// assert_eq!([&first[..], &second[..]].concat(), b"[1,           2]      ");
+/// ```
+pub fn strip_slice_stateful(
+    state: &mut StripState,
+    buf: &mut [u8],
+    settings: CommentSettings,
+) -> core::result::Result<(), StripError> {
+    if state.consumed == 0 {
+        state.state = start_input(buf, settings, &mut ());
+    }
+    let base = state.consumed;
+    state.consumed += buf.len();
+    state.lone_slashes = settings.lone_slashes;
+    strip_buf(&mut state.state, buf, settings)
+        .map_err(|err| StripError::new(err.kind(), base + err.offset()))
+}
+
+/// Strips `s` in place up to the first error, for showing a preview of text that is still
+/// being edited.
+///
+/// Returns the length of the prefix of `s` that was stripped successfully, and the error that
+/// stopped stripping, if any. When there is an error, the prefix ends where the string or
+/// comment containing it starts, and the rest of `s` is left unchanged.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_best_effort, StripErrorKind};
+///
+/// let mut s = String::from("{\"a\": 1, // one\n /* unterminated");
+/// let (len, err) = strip_best_effort(&mut s);
+///
+/// assert_eq!(len, 17);
+/// let err = err.unwrap();
+/// assert_eq!((err.kind(), err.offset()), (StripErrorKind::UnterminatedBlockComment, 17));
+/// assert_eq!(s, "{\"a\": 1,       \n /* unterminated");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_best_effort(s: &mut str) -> (usize, Option<StripError>) {
+    let settings = CommentSettings::all();
+    let mut state = Top;
+    let mut start = 0;
+    let mut err = None;
+    for (i, &c) in s.as_bytes().iter().enumerate() {
+        if state == Top {
+            start = i;
+        }
+        match transition(state, c, settings) {
+            Ok((next, _)) => state = next,
+            Err(kind) => {
+                err = Some(StripError::new(kind, i));
+                break;
+            }
+        }
+    }
+    let len = match err {
+        None if state.is_complete(settings) => s.len(),
+        None => {
+            err = Some(StripError::at_eof(state, start, s.len()));
+            start
+        }
+        Some(_) => start,
+    };
+    // Safety: the settings fill with spaces, so the text stays UTF-8.
+    let prefix = unsafe { s[..len].as_bytes_mut() };
+    // The prefix ends outside of any string or comment, so it always strips cleanly.
+    if let Err(e) = strip_slice(prefix, settings) {
+        return (0, Some(e));
+    }
+    (len, err)
+}
+
+/// Returns the byte offsets of the trailing commas that [`strip`] would remove, without
+/// modifying `s`.
+///
+/// Commas inside strings and comments are never reported.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::trailing_comma_positions;
+///
+/// let input = r#"{"a": [1, 2,], "b": 3, /* c */ }"#;
+/// assert_eq!(trailing_comma_positions(input).unwrap(), vec![11, 21]);
+/// ```
+#[cfg(feature = "std")]
+pub fn trailing_comma_positions(s: &str) -> Result<Vec<usize>> {
+    struct Positions(Vec<usize>);
+
+    impl Observer for Positions {
+        fn trailing_comma(&mut self, pos: usize) {
+            self.0.push(pos);
+        }
+    }
+
+    let mut positions = Positions(Vec::new());
+    let mut buf = s.as_bytes().to_vec();
+    strip_buf_observed(&mut Top, &mut buf, CommentSettings::all(), &mut positions)?;
+    Ok(positions.0)
+}
+
+/// Splits `s` into documents at lines consisting of `separator`, such as YAML's `---`, and
+/// strips each document independently.
+///
+/// A separator only counts at the start of a line outside strings and comments, and may be
+/// followed by `\n`, `\r\n`, a lone `\r`, or the end of input. Separator lines are not
+/// included in the returned documents, and a separator on the last line doesn't start an empty
+/// one.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_documents;
+///
+/// let input = "{\"a\": 1} // first\n---\n/* second */ {\"b\": \"---\"}";
+/// let docs = strip_documents(input, "---").unwrap();
+///
+/// assert_eq!(docs, ["{\"a\": 1}         \n", "             {\"b\": \"---\"}"]);
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_documents(s: &str, separator: &str) -> Result<Vec<String>> {
+    let bytes = s.as_bytes();
+    let settings = CommentSettings::all();
+    let mut docs = Vec::new();
+    let mut state = Top;
+    let mut doc_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let line_start = match i.checked_sub(1).map(|prev| bytes[prev]) {
+            None | Some(b'\n') => true,
+            Some(b'\r') => bytes[i] != b'\n',
+            Some(_) => false,
+        };
+        if state == Top && line_start {
+            if let Some(rest) = s[i..].strip_prefix(separator) {
+                let line_end = match rest.as_bytes() {
+                    [] => Some(0),
+                    [b'\n', ..] => Some(1),
+                    [b'\r', b'\n', ..] => Some(2),
+                    [b'\r', ..] => Some(1),
+                    _ => None,
+                };
+                if let Some(line_end) = line_end {
+                    docs.push(&s[doc_start..i]);
+                    i += separator.len() + line_end;
+                    doc_start = i;
+                    continue;
+                }
+            }
+        }
+        state = transition(state, bytes[i], settings)
+            .map_err(|kind| StripError::new(kind, i))?
+            .0;
+        i += 1;
+    }
+    if doc_start < s.len() || docs.is_empty() {
+        docs.push(&s[doc_start..]);
+    }
+
+    docs.into_iter()
+        .map(|doc| {
+            let mut doc = doc.to_owned();
+            strip(&mut doc)?;
+            Ok(doc)
+        })
+        .collect()
+}
+
+/// Strips comments and trailing commas from JSON inside a unified diff hunk, where each line
+/// starts with `+`, `-`, or a space.
+///
+/// The prefixes and line breaks are kept. The old side, made of the context and `-` lines, and
+/// the new side, made of the context and `+` lines, are each stripped as one continuous
+/// document, so a block comment may span several lines and a trailing comma may be followed by
+/// its closing bracket on the next line. Context lines are taken from the new side. Lines
+/// without a prefix, such as `@@` hunk headers, are not JSON and are left as they are.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_diff_hunk;
+///
+/// let mut s = String::from("+{\n+  \"a\": 1, /* added\n+  note */\n-  \"b\": 2 // old\n+}");
+/// strip_diff_hunk(&mut s).unwrap();
+/// assert_eq!(s, "+{\n+  \"a\": 1          \n+         \n-  \"b\": 2       \n+}");
+/// ```
+#[cfg(feature = "std")]
+pub fn strip_diff_hunk(s: &mut str) -> Result<()> {
+    // Safety: lines are copied back whole from stripped copies, which only replace ASCII bytes
+    // and whole characters in comments, so the text stays UTF-8.
+    let buf = unsafe { s.as_bytes_mut() };
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    while line_start < buf.len() {
+        let line_end = memchr::memchr(b'\n', &buf[line_start..])
+            .map_or(buf.len(), |offset| line_start + offset);
+        lines.push(line_start..line_end);
+        line_start = line_end + 1;
+    }
+    // Each side is stripped with the other side's lines and the unprefixed lines blanked, so
+    // offsets stay the same, and the prefixes hidden behind spaces can't separate a trailing
+    // comma from its bracket.
+    let strip_side = |this: u8| {
+        let mut side = buf.to_vec();
+        for line in &lines {
+            match side[line.start] {
+                c if c == this || c == b' ' => side[line.start] = b' ',
+                _ => side[line.clone()].fill(b' '),
+            }
+        }
+        let result = strip_buf(&mut Top, &mut side, CommentSettings::all());
+        (side, result)
+    };
+    let (old, old_result) = strip_side(b'-');
+    let (new, new_result) = strip_side(b'+');
+    // Only the lines are copied back, since block comments blank line breaks too.
+    for line in lines {
+        let prefix = buf[line.start];
+        let side = match prefix {
+            b'-' => &old,
+            b'+' | b' ' => &new,
+            _ => continue,
+        };
+        buf[line.start + 1..line.end].copy_from_slice(&side[line.start + 1..line.end]);
+    }
+    Ok(old_result.and(new_result)?)
+}
+
+/// Settings for `StripComments`
+///
+/// The default is for all comment types to be enabled.
+///
+/// Settings may be added in minor releases, so outside this crate the struct can't be built
+/// with a literal. Start from [`all`](Self::all), [`hash_only`](Self::hash_only),
+/// [`c_style`](Self::c_style) or the default and assign the fields to change, or use
+/// [`StripCommentsBuilder`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_comments_in_place, CommentSettings};
+///
+/// let mut settings = CommentSettings::all();
+/// settings.trailing_commas = false;
+///
+/// let mut s = String::from("[1, 2,] // two");
+/// strip_comments_in_place(&mut s, settings).unwrap();
+/// assert_eq!(s, "[1, 2,]       ");
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub struct CommentSettings {
+    /// True if c-style block comments (`/* ... */`) are removed.
+    pub block_comments: bool,
+    /// True if c-style `//` line comments are removed.
+    pub slash_line_comments: bool,
+    /// True if shell-style `#` line comments are removed.
+    pub hash_line_comments: bool,
+    /// True if trailing commas are removed. Comments around a kept comma are still removed
+    /// according to the other settings.
+    pub trailing_commas: bool,
+    /// True if commas that leave an empty element, as in `[1,,2]` or `[,1]`, are removed, so
+    /// that JSON5-style array holes are dropped: `[1,,2]` becomes `[1, 2]`. A run of commas
+    /// before a closing bracket is removed whole with
+    /// [`trailing_commas`](Self::trailing_commas).
+    ///
+    /// Otherwise only the last comma before a closing bracket is removed, so `[1,,]` becomes
+    /// `[1, ]`, and `[1,,2]` and `[,1]` are left alone.
+    pub elide_empty_elements: bool,
+    /// True if a `\` at the end of a line comment continues the comment onto the next line,
+    /// as in C and C++. `\` followed by `\r\n` is also a continuation. The line break itself
+    /// is kept either way.
+    pub line_comment_continuation: bool,
+    /// True if block comments nest, as in Rust and Swift, so `/* a /* b */ c */` is one
+    /// comment. Otherwise a block comment ends at the first `*/`.
+    ///
+    /// Nesting deeper than [`MAX_COMMENT_NESTING`] is an error.
+    pub nested_block_comments: bool,
+    /// True if U+2028 (LINE SEPARATOR) and U+2029 (PARAGRAPH SEPARATOR) end line comments, as
+    /// they do in JSON5 and JavaScript. Otherwise only `\n` and `\r` do.
+    ///
+    /// The separator is blanked along with the comment, since JSON does not count it as
+    /// whitespace. It ends the comment even after a `\` with
+    /// [`line_comment_continuation`](Self::line_comment_continuation).
+    pub unicode_line_separators: bool,
+    /// True if a `/` that starts neither `//` nor `/*` is kept as an ordinary byte, as in an
+    /// unquoted JSON5-style key like `foo/bar`, rather than being an
+    /// [`InvalidCommentStart`](StripErrorKind::InvalidCommentStart) error.
+    ///
+    /// A `/` is then only blanked once the byte after it shows that it starts a comment, so
+    /// [`StripComments`] and [`StripCommentsWriter`] hold one back at the end of a read or
+    /// write until more input arrives.
+    pub lone_slashes: bool,
+    /// True if raw control characters inside strings, such as a literal newline, carriage
+    /// return, or tab, are an error. JSON requires them to be escaped, but hand-edited files
+    /// often contain them.
+    ///
+    /// The error carries a [`StripError`] with the byte offset of the character.
+    pub forbid_control_chars_in_strings: bool,
+    /// True if [`StripComments`] ends without an error when the input ends inside a string,
+    /// so that everything read so far can still be shown. The error it would have returned,
+    /// with the offset where the string began, is kept for
+    /// [`StripComments::unterminated_string`].
+    ///
+    /// Input that ends inside a block comment is still an error.
+    pub recover_unterminated_strings: bool,
+    /// True if `` ` `` starts a string that ends at the next unescaped `` ` ``, like a
+    /// JavaScript template literal, so comment-like text inside it is kept.
+    ///
+    /// `${...}` interpolations are not parsed: everything up to the closing backtick, including
+    /// any comments inside an interpolation, is treated as string contents and kept.
+    pub backtick_strings: bool,
+    /// True if `'` starts a string that ends at the next unescaped `'`, as in JSON5, so
+    /// comment-like text inside it is kept. Backslash escapes work as in `"` strings, and
+    /// [`forbid_control_chars_in_strings`](Self::forbid_control_chars_in_strings) applies too.
+    pub single_quote_strings: bool,
+    /// The byte that starts and ends ordinary strings, `"` by default. Backslash escapes and
+    /// [`forbid_control_chars_in_strings`](Self::forbid_control_chars_in_strings) work the
+    /// same whatever it is.
+    ///
+    /// Unlike [`backtick_strings`](Self::backtick_strings) and
+    /// [`single_quote_strings`](Self::single_quote_strings), which add string kinds, this
+    /// replaces `"`, so with `` b'`' `` a `"` is an ordinary byte and comment-like text after
+    /// it is stripped. It should be an ASCII byte other than `/`, `#`, `\`, and `,`.
+    pub string_delimiter: u8,
+    /// True if a byte order mark (U+FEFF) at the very start of the input is replaced with
+    /// spaces.
+    ///
+    /// Only a leading mark is removed. A U+FEFF inside a string, raw or written as `\uFEFF`,
+    /// is string contents and kept, and one anywhere else is left alone for the JSON parser to
+    /// reject, since JSON does not count it as whitespace.
+    pub skip_bom: bool,
+    /// True if a shebang line, such as `#!/usr/bin/env node`, at the very start of the input is
+    /// removed, even with [`hash_line_comments`](Self::hash_line_comments) off. The line break
+    /// is kept. Only a `#!` at offset zero counts, not one after a byte order mark.
+    pub strip_leading_shebang: bool,
+    /// If set, block comments whose text after the `/*` starts with this prefix are kept
+    /// verbatim rather than blanked, such as `/*! ... */` license banners with
+    /// `CommentPrefix::new(b"!")`. An empty prefix keeps every block comment.
+    ///
+    /// The prefix is stored inline in a [`CommentPrefix`], so the settings stay `Copy` while the
+    /// prefix can still come from configuration read at runtime. It has no effect with
+    /// [`nested_block_comments`](Self::nested_block_comments). Kept comments are not counted in
+    /// [`StripStats`], and output containing them is not plain JSON. [`StripComments`] and
+    /// [`StripCommentsWriter`] hold back the opening of a block comment until the prefix shows
+    /// whether it is kept.
+    pub preserve_comment_prefix: Option<CommentPrefix>,
+    /// How text outside strings and comments is scanned. This only affects speed, never the
+    /// output.
+    pub scan_strategy: ScanStrategy,
+    /// The byte that replaces removed comments and commas, a space by default.
+    ///
+    /// It must be ASCII, or the in-place functions on `str` fail with
+    /// [`ErrorKind::InvalidInput`]. Anything but whitespace makes the output invalid JSON, so
+    /// other bytes, such as a visible marker or a `\0` to trim afterwards, are only for tools
+    /// that want to see what was removed, not for passing to a parser like `serde_json`.
+    pub fill: u8,
+}
+
+/// How [`strip_comments_in_place_with_top_level_commas`] treats a comma outside any object or
+/// array.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum TopLevelCommas {
+    /// Leave the comma alone.
+    #[default]
+    Allow,
+    /// Replace the comma with [`CommentSettings::fill`].
+    Strip,
+    /// Reject the input.
+    Error,
+}
+
+/// How [`CommentSettings::scan_strategy`] moves over text outside strings and comments.
+///
+/// Most of a typical document is keys, numbers, and whitespace that can't start a string,
+/// comment, or trailing comma. [`Table`](Self::Table) skips over runs of them with a lookup
+/// table, while [`Branch`](Self::Branch) feeds every byte through the state machine, which can
+/// predict better when such runs are short.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ScanStrategy {
+    /// [`Table`](Self::Table) for buffers of at least [`TABLE_SCAN_MIN_LEN`] bytes, otherwise
+    /// [`Branch`](Self::Branch).
+    #[default]
+    Auto,
+    /// Step through every byte.
+    Branch,
+    /// Skip runs of bytes that can't change the state.
+    Table,
+}
+
+/// The buffer length from which [`ScanStrategy::Auto`] uses the table.
+pub const TABLE_SCAN_MIN_LEN: usize = 256;
+
+/// The prefix of block comments to keep, for [`CommentSettings::preserve_comment_prefix`].
+///
+/// It holds up to [`MAX_LEN`](Self::MAX_LEN) bytes inline rather than borrowing them, so it is
+/// `Copy` like the rest of the settings.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::CommentPrefix;
+///
+/// let prefix = CommentPrefix::new(b"!").unwrap();
+/// assert_eq!(prefix.as_bytes(), b"!");
+///
+/// assert!(CommentPrefix::new(b"*/").is_none());
+/// assert!(CommentPrefix::new(&[b'!'; 17]).is_none());
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct CommentPrefix {
+    bytes: [u8; CommentPrefix::MAX_LEN],
+    len: u8,
+}
+
+impl CommentPrefix {
+    /// The longest prefix that can be kept.
+    pub const MAX_LEN: usize = 16;
+
+    /// Returns the prefix `prefix`, or `None` if it is longer than [`MAX_LEN`](Self::MAX_LEN)
+    /// bytes or contains `*/`, which would end the comment inside it.
+    pub const fn new(prefix: &[u8]) -> Option<Self> {
+        if prefix.len() > Self::MAX_LEN {
+            return None;
+        }
+        let mut bytes = [0; Self::MAX_LEN];
+        let mut i = 0;
+        while i < prefix.len() {
+            if prefix[i] == b'/' && i > 0 && prefix[i - 1] == b'*' {
+                return None;
+            }
+            bytes[i] = prefix[i];
+            i += 1;
+        }
+        Some(Self {
+            bytes,
+            len: prefix.len() as u8,
+        })
+    }
+
+    /// The bytes of the prefix.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+/// The bytes that can move the state machine out of [`Top`], or start a trailing comma.
+static TOP_SPECIAL: [bool; 256] = top_special(false);
+
+/// [`TOP_SPECIAL`] with the opening brackets that
+/// [`elide_empty_elements`](CommentSettings::elide_empty_elements) looks after.
+static TOP_SPECIAL_OR_OPEN: [bool; 256] = top_special(true);
+
+const fn top_special(open: bool) -> [bool; 256] {
+    let mut table = [false; 256];
+    table[b'"' as usize] = true;
+    table[b'`' as usize] = true;
+    table[b'\'' as usize] = true;
+    table[b'/' as usize] = true;
+    table[b'#' as usize] = true;
+    table[b',' as usize] = true;
+    table[b'[' as usize] = open;
+    table[b'{' as usize] = open;
+    table
+}
+
+impl Default for CommentSettings {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl CommentSettings {
+    /// Enable all comment Styles
+    pub const fn all() -> Self {
+        Self {
+            block_comments: true,
+            slash_line_comments: true,
+            hash_line_comments: true,
+            trailing_commas: true,
+            elide_empty_elements: false,
+            line_comment_continuation: false,
+            nested_block_comments: false,
+            unicode_line_separators: false,
+            lone_slashes: false,
+            forbid_control_chars_in_strings: false,
+            recover_unterminated_strings: false,
+            backtick_strings: false,
+            single_quote_strings: false,
+            string_delimiter: b'"',
+            skip_bom: false,
+            strip_leading_shebang: false,
+            preserve_comment_prefix: None,
+            scan_strategy: ScanStrategy::Auto,
+            fill: b' ',
+        }
+    }
+    /// Only allow line comments starting with `#`
+    pub const fn hash_only() -> Self {
+        Self {
+            hash_line_comments: true,
+            block_comments: false,
+            slash_line_comments: false,
+            trailing_commas: false,
+            elide_empty_elements: false,
+            line_comment_continuation: false,
+            nested_block_comments: false,
+            unicode_line_separators: false,
+            lone_slashes: false,
+            forbid_control_chars_in_strings: false,
+            recover_unterminated_strings: false,
+            backtick_strings: false,
+            single_quote_strings: false,
+            string_delimiter: b'"',
+            skip_bom: false,
+            strip_leading_shebang: false,
+            preserve_comment_prefix: None,
+            scan_strategy: ScanStrategy::Auto,
+            fill: b' ',
+        }
+    }
+    /// Only allow "c-style" comments.
+    ///
+    /// Specifically, line comments beginning with `//` and
+    /// block comment like `/* ... */`.
+    pub const fn c_style() -> Self {
+        Self {
+            block_comments: true,
+            slash_line_comments: true,
+            hash_line_comments: false,
+            trailing_commas: true,
+            elide_empty_elements: false,
+            line_comment_continuation: false,
+            nested_block_comments: false,
+            unicode_line_separators: false,
+            lone_slashes: false,
+            forbid_control_chars_in_strings: false,
+            recover_unterminated_strings: false,
+            backtick_strings: false,
+            single_quote_strings: false,
+            string_delimiter: b'"',
+            skip_bom: false,
+            strip_leading_shebang: false,
+            preserve_comment_prefix: None,
+            scan_strategy: ScanStrategy::Auto,
+            fill: b' ',
+        }
+    }
+
+    /// Create a new `StripComments` for `input`, using these settings.
+    ///
+    /// Transform `input` into a [`Read`] that strips out comments.
+    /// The types of comments to support are determined by the configuration of
+    /// `self`.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// use json_strip_comments::CommentSettings;
+    /// use std::io::Read;
+    ///
+    /// let input = r#"{
+    /// // c line comment
+    /// "a": "b"
+    /// /** multi line
+    /// comment
+    /// */ }"#;
+    ///
+    /// let mut stripped = String::new();
+    /// CommentSettings::c_style().strip_comments(input.as_bytes()).read_to_string(&mut stripped).unwrap();
+    ///
+    /// assert_eq!(stripped, "{
+    ///                  \n\"a\": \"b\"
+    ///                           }");
+    /// ```
+    ///
+    /// ```
+    /// use json_strip_comments::CommentSettings;
+    /// use std::io::Read;
+    ///
+    /// let input = r#"{
+    /// ## shell line comment
+    /// "a": "b"
+    /// }"#;
+    ///
+    /// let mut stripped = String::new();
+    /// CommentSettings::hash_only().strip_comments(input.as_bytes()).read_to_string(&mut stripped).unwrap();
+    ///
+    /// assert_eq!(stripped, "{
+    ///                     \n\"a\": \"b\"\n}");
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn strip_comments<I: Read>(self, input: I) -> StripComments<I> {
+        StripComments::with_settings(self, input)
+    }
+
+    /// The bytes of [`preserve_comment_prefix`](Self::preserve_comment_prefix), empty if unset.
+    fn preserved_prefix(&self) -> &[u8] {
+        self.preserve_comment_prefix
+            .as_ref()
+            .map_or(&[], CommentPrefix::as_bytes)
+    }
+}
+
+/// The result of the stripping core, which needs neither `std` nor an allocator.
+type StripResult<T> = core::result::Result<T, StripError>;
+
+/// How the search for the end of a possible trailing comma ended.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Lookahead {
+    /// A closing bracket came next, so the comma is trailing.
+    Trailing,
+    /// Something else came next.
+    NotTrailing,
+    /// The buffer ended first.
+    OutOfInput,
+}
+
+/// Steps from `buf[*i]` over whitespace and comments, until the byte that decides whether the
+/// comma before them is trailing, and leaves `i` at that byte.
+///
+/// With [`CommentSettings::elide_empty_elements`], commas on the way are removed and stepped
+/// over too, as they would leave an empty element.
+fn lookahead(
+    state: &mut State,
+    buf: &mut [u8],
+    i: &mut usize,
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) -> StripResult<Lookahead> {
+    while *i < buf.len() {
+        let lone_slash = ends_lone_slash(*state, buf[*i], settings);
+        let was_top = *state == Top;
+        *state = step(*state, buf, i, settings, observer)?;
+        if lone_slash {
+            // The `/` before was a value, not a comment.
+            return Ok(Lookahead::NotTrailing);
+        }
+        // Comment starts have been blanked by `step`, so they count as whitespace here.
+        if was_top && !matches!(*state, InComment | InLineComment) && !buf[*i].is_ascii_whitespace()
+        {
+            if settings.elide_empty_elements && buf[*i] == b',' {
+                buf[*i] = settings.fill;
+                observer.trailing_comma(*i);
+                *i += 1;
+                continue;
+            }
+            return Ok(if matches!(buf[*i], b'}' | b']') {
+                Lookahead::Trailing
+            } else {
+                Lookahead::NotTrailing
+            });
+        }
+        *i += 1;
+    }
+    Ok(Lookahead::OutOfInput)
+}
+
+/// True if what follows `c` at the top level must be looked at before it can be handed out: a
+/// comma that may be trailing, or with [`CommentSettings::elide_empty_elements`] a comma or
+/// opening bracket that may be followed by commas to remove.
+fn needs_lookahead(c: u8, settings: CommentSettings) -> bool {
     match c {
-        b'"' => Top,
-        b'\\' => StringEscape,
-        _ => InString,
+        b',' => settings.trailing_commas || settings.elide_empty_elements,
+        b'[' | b'{' => settings.elide_empty_elements,
+        _ => false,
+    }
+}
+
+fn strip_buf(state: &mut State, buf: &mut [u8], settings: CommentSettings) -> StripResult<()> {
+    strip_buf_observed(state, buf, settings, &mut ())
+}
+
+/// Receives notifications about what [`strip_buf_observed`] removes.
+///
+/// Positions are indexes into the buffer being stripped. A comment spans from its
+/// `comment_start` up to (excluding) its `comment_end`; a line comment that runs to the end of
+/// the buffer has no `comment_end`.
+trait Observer {
+    fn trailing_comma(&mut self, _pos: usize) {}
+    fn comment_start(&mut self, _pos: usize) {}
+    /// Called once the kind of the comment last started is known.
+    fn comment_kind(&mut self, _kind: CommentKind) {}
+    fn comment_end(&mut self, _pos: usize) {}
+    fn string_start(&mut self, _pos: usize) {}
+    /// Called once the block comment last started turns out to be kept with
+    /// [`CommentSettings::preserve_comment_prefix`]. Its opening has been put back, except for
+    /// the `before_input` bytes of it that came before the buffer.
+    fn comment_preserved(&mut self, _before_input: usize) {}
+}
+
+impl Observer for () {}
+
+/// Where the last string or comment began, for reporting one the input ends inside.
+#[derive(Default)]
+struct LastStart(usize);
+
+impl Observer for LastStart {
+    fn comment_start(&mut self, pos: usize) {
+        self.0 = pos;
+    }
+
+    fn string_start(&mut self, pos: usize) {
+        self.0 = pos;
+    }
+}
+
+fn strip_buf_observed(
+    state: &mut State,
+    buf: &mut [u8],
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) -> StripResult<()> {
+    // At the end of the whole input, a comma with nothing after it is not trailing.
+    strip_chunk(state, buf, 0, settings, observer).map(drop)
+}
+
+/// Strips `buf[start..]`, returning the position of a comma that is only followed by
+/// whitespace and comments up to the end of `buf`, so that whether it is trailing depends on
+/// input after `buf`.
+fn strip_chunk(
+    state: &mut State,
+    buf: &mut [u8],
+    start: usize,
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) -> StripResult<Option<usize>> {
+    // The table only knows about `"` strings.
+    let table = settings.string_delimiter == b'"'
+        && match settings.scan_strategy {
+            ScanStrategy::Auto => buf.len() >= TABLE_SCAN_MIN_LEN,
+            ScanStrategy::Branch => false,
+            ScanStrategy::Table => true,
+        };
+    let special = if settings.elide_empty_elements {
+        &TOP_SPECIAL_OR_OPEN
+    } else {
+        &TOP_SPECIAL
+    };
+    let mut i = start;
+    while i < buf.len() {
+        if table && *state == Top {
+            match buf[i..].iter().position(|&c| special[c as usize]) {
+                Some(skip) => i += skip,
+                None => break,
+            }
+        }
+        let cur = i;
+        let was_top = *state == Top || ends_lone_slash(*state, buf[i], settings);
+        *state = step(*state, buf, &mut i, settings, observer)?;
+        if was_top && needs_lookahead(buf[cur], settings) {
+            i += 1;
+            match lookahead(state, buf, &mut i, settings, observer)? {
+                Lookahead::Trailing if settings.trailing_commas && buf[cur] == b',' => {
+                    buf[cur] = settings.fill;
+                    observer.trailing_comma(cur);
+                }
+                // The comma or bracket that came next needs its own look ahead. Stepping it
+                // again at the top level is harmless.
+                Lookahead::NotTrailing if needs_lookahead(buf[i], settings) => continue,
+                Lookahead::Trailing | Lookahead::NotTrailing => {}
+                Lookahead::OutOfInput => return Ok(Some(cur)),
+            }
+        }
+        i += 1;
+    }
+    // A `/` kept at the end may yet turn out to start a comment.
+    Ok(buf
+        .len()
+        .checked_sub(1)
+        .filter(|_| *state == InComment && settings.lone_slashes))
+}
+
+/// Advances the state machine over `buf[*i]`.
+///
+/// Comment states may consume several bytes at once, leaving `i` at the last byte consumed.
+#[inline]
+fn step(
+    state: State,
+    buf: &mut [u8],
+    i: &mut usize,
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) -> StripResult<State> {
+    let state = if ends_lone_slash(state, buf[*i], settings) {
+        Top
+    } else {
+        state
+    };
+    let new_state = match state {
+        InBlockComment => consume_block_comments(buf, i, settings.fill),
+        InPreservedComment => consume_preserved_comments(buf, i),
+        InLineComment => consume_line_comments(buf, i, settings),
+        _ => {
+            if !settings.forbid_control_chars_in_strings {
+                skip_string_contents(state, buf, i, settings.string_delimiter);
+            }
+            let (new_state, action) = match transition(state, buf[*i], settings) {
+                Ok(next) => next,
+                Err(kind) => return Err(StripError::new(kind, *i)),
+            };
+            if action == Blank {
+                buf[*i] = settings.fill;
+            }
+            new_state
+        }
+    };
+    match (state, new_state) {
+        (Top, InComment) if !settings.lone_slashes => observer.comment_start(*i),
+        (Top, InLineComment) => {
+            observer.comment_start(*i);
+            observer.comment_kind(CommentKind::Hash);
+        }
+        (InComment, _) => {
+            if settings.lone_slashes && *i > 0 {
+                // The `/` was kept in case it did not start a comment. One that ended the
+                // previous input is the caller's to blank.
+                buf[*i - 1] = settings.fill;
+                observer.comment_start(*i - 1);
+            }
+            observer.comment_kind(if new_state == InLineComment {
+                CommentKind::Slash
+            } else {
+                CommentKind::Block
+            });
+            if new_state == InPreservedComment {
+                preserve_opening(buf, *i, settings, observer);
+            }
+        }
+        (MaybePreservedComment(_), InPreservedComment) => {
+            preserve_opening(buf, *i, settings, observer);
+        }
+        (Top, InString | InBacktickString | InSingleQuoteString) => observer.string_start(*i),
+        (MaybeCommentEnd | MaybeNestedCommentEnd(_) | MaybePreservedComment(_), Top) => {
+            observer.comment_end(*i + 1);
+        }
+        (InLineComment | LineCommentContinuation, Top) => observer.comment_end(*i),
+        (MaybeLineSeparator(_), Top) => observer.comment_end(*i + 1),
+        _ => {}
+    }
+    Ok(new_state)
+}
+
+/// Puts back the opening of a block comment that `buf[i]` shows is kept with
+/// [`CommentSettings::preserve_comment_prefix`], which was blanked until then, as far as it is
+/// in `buf`, and tells `observer` how much of it came before.
+fn preserve_opening(
+    buf: &mut [u8],
+    i: usize,
+    settings: CommentSettings,
+    observer: &mut impl Observer,
+) {
+    let prefix = settings.preserved_prefix();
+    // Everything but the last byte of `/*` and the prefix, which is `buf[i]`.
+    let blanked = 1 + prefix.len();
+    let in_buf = blanked.min(i);
+    restore_opening(&mut buf[i - in_buf..i], prefix, blanked - in_buf);
+    observer.comment_preserved(blanked - in_buf);
+}
+
+/// Writes the opening `/*` and `prefix` of a kept comment into `dst`, skipping its first `skip`
+/// bytes.
+fn restore_opening(dst: &mut [u8], prefix: &[u8], skip: usize) {
+    for (byte, &c) in dst.iter_mut().zip(b"/*".iter().chain(prefix).skip(skip)) {
+        *byte = c;
+    }
+}
+
+/// How many bytes at the end of the input so far may be the opening of a comment kept with
+/// [`CommentSettings::preserve_comment_prefix`], blanked for now, so that [`StripComments`] and
+/// [`StripCommentsWriter`] must hold them back.
+#[cfg(feature = "std")]
+fn pending_opening(state: State, settings: CommentSettings) -> usize {
+    match state {
+        InComment if settings.block_comments && settings.preserve_comment_prefix.is_some() => 1,
+        MaybePreservedComment(matched) => usize::from(matched) + 2,
+        _ => 0,
+    }
+}
+
+/// True if `c`, after a `/` kept with [`CommentSettings::lone_slashes`], shows that the `/` did
+/// not start a comment, so that `c` is back at the top level.
+#[inline]
+fn ends_lone_slash(state: State, c: u8, settings: CommentSettings) -> bool {
+    state == InComment
+        && settings.lone_slashes
+        && !((c == b'*' && settings.block_comments) || (c == b'/' && settings.slash_line_comments))
+}
+
+/// Inside a string, moves `i` to the next closing quote or backslash, or to the last byte if
+/// there is neither, since [`transition`] keeps every byte in between without changing state.
+///
+/// Raw control characters must be checked one at a time, so the caller skips this when
+/// [`CommentSettings::forbid_control_chars_in_strings`] is set.
+#[inline]
+fn skip_string_contents(state: State, buf: &[u8], i: &mut usize, delimiter: u8) {
+    let quote = match state {
+        InString => delimiter,
+        InSingleQuoteString => b'\'',
+        InBacktickString => b'`',
+        _ => return,
+    };
+    match memchr::memchr2(quote, b'\\', &buf[*i..]) {
+        Some(offset) => *i += offset,
+        None => *i = buf.len() - 1,
+    }
+}
+
+/// What happens to a byte after [`transition`].
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+enum Action {
+    Keep,
+    Blank,
+}
+
+use Action::{Blank, Keep};
+
+/// The state machine for a single byte, free of side effects.
+///
+/// [`step`] drives this, with bulk fast paths for the comment states that must agree with it
+/// byte for byte.
+#[inline]
+fn transition(
+    state: State,
+    c: u8,
+    settings: CommentSettings,
+) -> core::result::Result<(State, Action), StripErrorKind> {
+    Ok(match (state, c) {
+        (Top, c) if c == settings.string_delimiter => (InString, Keep),
+        (Top, b'/') if settings.lone_slashes => (InComment, Keep),
+        (Top, b'/') if settings.block_comments || settings.slash_line_comments => {
+            (InComment, Blank)
+        }
+        (Top, b'/') => (InComment, Keep),
+        (Top, b'#') if settings.hash_line_comments => (InLineComment, Blank),
+        (Top, b'`') if settings.backtick_strings => (InBacktickString, Keep),
+        (Top, b'\'') if settings.single_quote_strings => (InSingleQuoteString, Keep),
+        (Top, _) => (Top, Keep),
+
+        (InString, c) if c == settings.string_delimiter => (Top, Keep),
+        (InString | StringEscape, ..0x20) if settings.forbid_control_chars_in_strings => {
+            return Err(StripErrorKind::ControlCharInString)
+        }
+        (InString, b'\\') => (StringEscape, Keep),
+        (InString, _) | (StringEscape, _) => (InString, Keep),
+
+        (InBacktickString, b'`') => (Top, Keep),
+        (InBacktickString, b'\\') => (BacktickEscape, Keep),
+        (InBacktickString, _) | (BacktickEscape, _) => (InBacktickString, Keep),
+
+        (InSingleQuoteString, b'\'') => (Top, Keep),
+        (InSingleQuoteString | SingleQuoteEscape, ..0x20)
+            if settings.forbid_control_chars_in_strings =>
+        {
+            return Err(StripErrorKind::ControlCharInString)
+        }
+        (InSingleQuoteString, b'\\') => (SingleQuoteEscape, Keep),
+        (InSingleQuoteString, _) | (SingleQuoteEscape, _) => (InSingleQuoteString, Keep),
+
+        (InComment, b'*') if settings.block_comments && settings.nested_block_comments => {
+            (InNestedComment(1), Blank)
+        }
+        (InComment, b'*') if settings.block_comments => match settings.preserve_comment_prefix {
+            None => (InBlockComment, Blank),
+            Some(prefix) if prefix.len == 0 => (InPreservedComment, Keep),
+            Some(_) => (MaybePreservedComment(0), Blank),
+        },
+        (InComment, b'/') if settings.slash_line_comments => (InLineComment, Blank),
+        (InComment, c) if settings.lone_slashes => return transition(Top, c, settings),
+        (InComment, _) => return Err(StripErrorKind::InvalidCommentStart),
+
+        (InBlockComment, b'*') | (MaybeCommentEnd, b'*') => (MaybeCommentEnd, Blank),
+        (InBlockComment, _) => (InBlockComment, Blank),
+        (MaybeCommentEnd, b'/') => (Top, Blank),
+        (MaybeCommentEnd, _) => (InBlockComment, Blank),
+
+        (InNestedComment(depth) | MaybeNestedCommentEnd(depth), b'*') => {
+            (MaybeNestedCommentEnd(depth), Blank)
+        }
+        (InNestedComment(depth) | MaybeNestedCommentStart(depth), b'/') => {
+            (MaybeNestedCommentStart(depth), Blank)
+        }
+        (MaybeNestedCommentEnd(1), b'/') => (Top, Blank),
+        (MaybeNestedCommentEnd(depth), b'/') => (InNestedComment(depth - 1), Blank),
+        (MaybeNestedCommentStart(MAX_COMMENT_NESTING), b'*') => {
+            return Err(StripErrorKind::CommentNestingTooDeep)
+        }
+        (MaybeNestedCommentStart(depth), b'*') => (InNestedComment(depth + 1), Blank),
+        (
+            InNestedComment(depth) | MaybeNestedCommentEnd(depth) | MaybeNestedCommentStart(depth),
+            _,
+        ) => (InNestedComment(depth), Blank),
+
+        (MaybePreservedComment(matched), c) => {
+            let prefix = settings.preserved_prefix();
+            let next = usize::from(matched);
+            if prefix.get(next) == Some(&c) {
+                if next + 1 == prefix.len() {
+                    (InPreservedComment, Keep)
+                } else {
+                    (MaybePreservedComment(matched + 1), Blank)
+                }
+            } else {
+                // Not kept after all, so the prefix so far was ordinary comment text, whose
+                // last `*` may start the end of the comment.
+                let last = next.checked_sub(1).and_then(|last| prefix.get(last));
+                let so_far = if last == Some(&b'*') {
+                    MaybeCommentEnd
+                } else {
+                    InBlockComment
+                };
+                return transition(so_far, c, settings);
+            }
+        }
+        (InPreservedComment | MaybePreservedCommentEnd, b'*') => (MaybePreservedCommentEnd, Keep),
+        (MaybePreservedCommentEnd, b'/') => (Top, Keep),
+        (InPreservedComment | MaybePreservedCommentEnd, _) => (InPreservedComment, Keep),
+
+        // A lone `\r` ends a line too, and the `\n` of a `\r\n` is then whitespace.
+        (InLineComment, b'\n' | b'\r') => (Top, Keep),
+        // U+2028 and U+2029 are E2 80 A8 and E2 80 A9 in UTF-8.
+        (InLineComment | LineCommentContinuation, 0xE2) if settings.unicode_line_separators => {
+            (MaybeLineSeparator(1), Blank)
+        }
+        (MaybeLineSeparator(1), 0x80) => (MaybeLineSeparator(2), Blank),
+        (MaybeLineSeparator(2), 0xA8 | 0xA9) => (Top, Blank),
+        (MaybeLineSeparator(_), _) => return transition(InLineComment, c, settings),
+        (LineCommentContinuation, b'\n') => (InLineComment, Keep),
+        (InLineComment | LineCommentContinuation, b'\\') if settings.line_comment_continuation => {
+            (LineCommentContinuation, Blank)
+        }
+        // Line ends are kept after a continuation too, and `\r\n` is one line end.
+        (LineCommentContinuation, b'\r') => (LineCommentContinuation, Keep),
+        (InLineComment | LineCommentContinuation, _) => (InLineComment, Blank),
+    })
+}
+
+#[inline]
+fn consume_line_comments(buf: &mut [u8], i: &mut usize, settings: CommentSettings) -> State {
+    let cur = *i;
+    let end = if settings.unicode_line_separators {
+        memchr::memchr3(b'\n', b'\r', 0xE2, &buf[*i..])
+    } else {
+        memchr::memchr2(b'\n', b'\r', &buf[*i..])
+    };
+    match end {
+        Some(offset) if buf[*i + offset] == 0xE2 => {
+            *i += offset;
+            buf[cur..=*i].fill(settings.fill);
+            MaybeLineSeparator(1)
+        }
+        Some(offset) => {
+            *i += offset;
+            let continued =
+                settings.line_comment_continuation && buf[cur..*i].last() == Some(&b'\\');
+            buf[cur..*i].fill(settings.fill);
+            match (continued, buf[*i]) {
+                (false, _) => Top,
+                (true, b'\n') => InLineComment,
+                // A `\r` may be followed by the `\n` of a `\r\n`.
+                (true, _) => LineCommentContinuation,
+            }
+        }
+        None => {
+            *i = buf.len() - 1;
+            let continued = settings.line_comment_continuation && buf[cur..].last() == Some(&b'\\');
+            buf[cur..].fill(settings.fill);
+            if continued {
+                LineCommentContinuation
+            } else {
+                InLineComment
+            }
+        }
+    }
+}
+
+#[inline]
+fn consume_preserved_comments(buf: &[u8], i: &mut usize) -> State {
+    match memchr::memchr(b'*', &buf[*i..]) {
+        Some(offset) => {
+            *i += offset;
+            MaybePreservedCommentEnd
+        }
+        None => {
+            *i = buf.len() - 1;
+            InPreservedComment
+        }
+    }
+}
+
+#[inline]
+fn consume_block_comments(buf: &mut [u8], i: &mut usize, fill: u8) -> State {
+    let cur = *i;
+    match memchr::memchr(b'*', &buf[*i..]) {
+        Some(offset) => {
+            *i += offset;
+            buf[cur..=*i].fill(fill);
+            MaybeCommentEnd
+        }
+        None => {
+            *i = buf.len() - 1;
+            buf[cur..].fill(fill);
+            InBlockComment
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{ErrorKind, Read};
+
+    fn strip_string(input: &str) -> String {
+        let mut out = String::new();
+        let count = StripComments::new(input.as_bytes())
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(count, input.len());
+        out
+    }
+
+    /// Hands out the inner bytes at most `chunk` at a time, to exercise buffer boundaries.
+    struct Chunked<'a> {
+        bytes: &'a [u8],
+        chunk: usize,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.chunk.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    fn strip_chunked(input: &str, chunk: usize) -> std::io::Result<String> {
+        let mut out = String::new();
+        StripComments::new(Chunked {
+            bytes: input.as_bytes(),
+            chunk,
+        })
+        .read_to_string(&mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn block_comments() {
+        let json = r#"{/* Comment */"hi": /** abc */ "bye"}"#;
+        let stripped = strip_string(json);
+        assert_eq!(stripped, r#"{             "hi":            "bye"}"#);
+    }
+
+    #[test]
+    fn block_comments_with_possible_end() {
+        let json = r#"{/* Comment*PossibleEnd */"hi": /** abc */ "bye"}"#;
+        let stripped = strip_string(json);
+        assert_eq!(
+            stripped,
+            r#"{                         "hi":            "bye"}"#
+        );
+    }
+
+    // See https://github.com/tmccombs/json-comments-rs/issues/12
+    // Make sure we can parse a block comment that ends with more than one "*"
+    #[test]
+    fn doc_comment() {
+        let json = r##"/** C **/ { "foo": 123 }"##;
+        let stripped = strip_string(json);
+        assert_eq!(stripped, r##"          { "foo": 123 }"##);
+    }
+
+    #[test]
+    fn line_comments() {
+        let json = r#"{
+            // line comment
+            "a": 4,
+            # another
+        }"#;
+
+        let expected = "{
+                           \n            \"a\": 4 
+                     \n        }";
+
+        assert_eq!(strip_string(json), expected);
+    }
+
+    #[test]
+    fn incomplete_string() {
+        let json = r#""foo"#;
+        let mut stripped = String::new();
+
+        let err = StripComments::new(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incomplete_comment() {
+        let json = "/* foo ";
+        let mut stripped = String::new();
+
+        let err = StripComments::new(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incomplete_comment2() {
+        let json = "/* foo *";
+        let mut stripped = String::new();
+
+        let err = StripComments::new(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn incomplete_input_in_place_strict() {
+        let settings = CommentSettings::all();
+        for (input, kind, offset) in [
+            ("[] /*", StripErrorKind::UnterminatedBlockComment, 3),
+            ("[] /* foo *", StripErrorKind::UnterminatedBlockComment, 3),
+            ("[\"a\", \"b", StripErrorKind::UnterminatedString, 6),
+            ("[1] /", StripErrorKind::InvalidCommentStart, 5),
+        ] {
+            // The lenient version strips what it can and succeeds.
+            strip_comments_in_place(&mut String::from(input), settings).unwrap();
+
+            let mut reader = StripComments::new(input.as_bytes());
+            let err = reader.read_to_string(&mut String::new()).unwrap_err();
+            let expected = StripError::new(kind, offset);
+            assert_eq!(strip_error(&err), expected, "{input}");
+            let err = strip_comments_in_place_strict(&mut String::from(input), settings);
+            assert_eq!(strip_error(&err.unwrap_err()), expected, "{input}");
+            let err = strip_slice_strict(&mut input.as_bytes().to_vec(), settings);
+            assert_eq!(err, Err(expected), "{input}");
+        }
+
+        let mut s = String::from("[1,] // done");
+        strip_comments_in_place_strict(&mut s, settings).unwrap();
+        assert_eq!(s, "[1 ]        ");
+    }
+
+    #[test]
+    fn strip_slice_stateful_at_every_split() {
+        let settings = CommentSettings::all();
+        let input = b"{\"a\": \"/* b */\", // c\n\"d\": [1, /* e */ 2]} # f";
+        let mut expected = input.to_vec();
+        strip_slice(&mut expected, settings).unwrap();
+        for split in 0..=input.len() {
+            let (mut head, mut tail) = (input[..split].to_vec(), input[split..].to_vec());
+            let mut state = StripState::new();
+            strip_slice_stateful(&mut state, &mut head, settings).unwrap();
+            strip_slice_stateful(&mut state, &mut tail, settings).unwrap();
+            assert!(state.is_complete());
+            assert_eq!([head, tail].concat(), expected, "split at {split}");
+        }
+
+        // A comma at the end of a slice is not known to be trailing.
+        let mut state = StripState::default();
+        let mut first = *b"[1,";
+        strip_slice_stateful(&mut state, &mut first, settings).unwrap();
+        assert_eq!(&first, b"[1,");
+
+        let err = strip_slice_stateful(&mut state, &mut [b'/', b'x'], settings).unwrap_err();
+        assert_eq!(err, StripError::new(StripErrorKind::InvalidCommentStart, 4));
+    }
+
+    #[test]
+    fn no_hash_comments() {
+        let json = r#"# bad comment
+        {"a": "b"}"#;
+        let mut stripped = String::new();
+        CommentSettings::c_style()
+            .strip_comments(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap();
+        assert_eq!(stripped, json);
+    }
+
+    #[test]
+    fn no_slash_line_comments() {
+        let json = r#"// bad comment
+        {"a": "b"}"#;
+        let mut stripped = String::new();
+        let err = CommentSettings::hash_only()
+            .strip_comments(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn no_block_comments() {
+        let json = r#"/* bad comment */ {"a": "b"}"#;
+        let mut stripped = String::new();
+        let err = CommentSettings::hash_only()
+            .strip_comments(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn keep_all() {
+        let original = String::from(
+            r#"
+     {
+         "name": /* full */ "John Doe",
+         "age": 43, # hash line comment
+         "phones": [
+             "+44 1234567", // work phone
+             "+44 2345678", // home phone
+         ], /** comment **/
+     }"#,
+        );
+        let mut changed = original.clone();
+        let _ = strip_comments_in_place(
+            &mut changed,
+            CommentSettings {
+                block_comments: false,
+                slash_line_comments: false,
+                hash_line_comments: false,
+                trailing_commas: false,
+                elide_empty_elements: false,
+                line_comment_continuation: false,
+                nested_block_comments: false,
+                unicode_line_separators: false,
+                lone_slashes: false,
+                forbid_control_chars_in_strings: false,
+                recover_unterminated_strings: false,
+                backtick_strings: false,
+                single_quote_strings: false,
+                string_delimiter: b'"',
+                skip_bom: false,
+                strip_leading_shebang: false,
+                preserve_comment_prefix: None,
+                scan_strategy: ScanStrategy::Auto,
+                fill: b' ',
+            },
+        );
+        assert_eq!(original, changed);
+    }
+
+    #[test]
+    fn strip_in_place() {
+        let mut json = String::from(r#"{/* Comment */"hi": /** abc */ "bye"}"#);
+        strip_comments_in_place(&mut json, CommentSettings::default()).unwrap();
+        assert_eq!(json, r#"{             "hi":            "bye"}"#);
+    }
+
+    #[test]
+    fn trailing_comma() {
+        let mut json = String::from(
+            r#"{
+            "a1": [1,],
+            "a2": [1,/* x */],
+            "a3": [
+                1, // x
+            ],
+            "o1": {v:1,},
+            "o2": {v:1,/* x */},
+            "o3": {
+                "v":1, // x
+            },
+            # another
+        }"#,
+        );
+        strip_comments_in_place(&mut json, CommentSettings::default()).unwrap();
+
+        let expected = r#"{
+            "a1": [1 ],
+            "a2": [1        ],
+            "a3": [
+                1
+            ],
+            "o1": {v:1 },
+            "o2": {v:1        },
+            "o3": {
+                "v":1
+            }
+        }"#;
+
+        assert_eq!(
+            json.replace(|s: char| s.is_ascii_whitespace(), ""),
+            expected.replace(|s: char| s.is_ascii_whitespace(), "")
+        );
+    }
+
+    #[test]
+    fn trailing_comma_before_comment() {
+        let cases = [
+            ("[false,/*x*/]", "[false      ]"),
+            ("[false, //x\n ]", "[false     \n ]"),
+            ("{\"a\": 1, #x\n }", "{\"a\": 1    \n }"),
+            ("[1, /*x*/ //y\n #z\n ]", "[1           \n   \n ]"),
+            // A comment between a comma and the next value leaves the comma alone.
+            ("[1,/*x*/2]", "[1,     2]"),
+            ("[1, //x\n 2]", "[1,    \n 2]"),
+        ];
+        for (input, expected) in cases {
+            let mut json = String::from(input);
+            strip(&mut json).unwrap();
+            assert_eq!(json, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn keep_trailing_commas() {
+        let settings = CommentSettings {
+            trailing_commas: false,
+            ..CommentSettings::default()
+        };
+        let input = "{\"a\": [1, /* x */], \"b\": {\"c\": 2, // y\n}, # z\n}";
+        let expected = "{\"a\": [1,        ], \"b\": {\"c\": 2,     \n},    \n}";
+        let mut json = String::from(input);
+        strip_comments_in_place(&mut json, settings).unwrap();
+        assert_eq!(json, expected);
+        for chunk in 1..=4 {
+            let mut out = String::new();
+            StripComments::with_settings(
+                settings,
+                Chunked {
+                    bytes: input.as_bytes(),
+                    chunk,
+                },
+            )
+            .read_to_string(&mut out)
+            .unwrap();
+            assert_eq!(out, expected, "chunk {chunk}");
+        }
+    }
+
+    #[test]
+    fn collect_diagnostics() {
+        use StripErrorKind::*;
+        let settings = CommentSettings {
+            forbid_control_chars_in_strings: true,
+            nested_block_comments: true,
+            ..CommentSettings::all()
+        };
+        let too_deep = "/*".repeat(usize::from(MAX_COMMENT_NESTING) + 1);
+        let cases = [
+            ("[1, 2] // ok", vec![]),
+            (
+                "[\"a\tb\", \"\n\"] /x",
+                vec![
+                    (ControlCharInString, 3),
+                    (ControlCharInString, 9),
+                    (InvalidCommentStart, 14),
+                ],
+            ),
+            ("[1] /", vec![(InvalidCommentStart, 5)]),
+            ("[\"a\\\n\\\\\"] //", vec![(ControlCharInString, 4)]),
+            ("[\"a\", \"b", vec![(UnterminatedString, 6)]),
+        ];
+        for (input, expected) in cases {
+            let mut s = String::from(input);
+            let diagnostics = strip_comments_collect_diagnostics(&mut s, settings).unwrap();
+            let found: Vec<_> = diagnostics.iter().map(|d| (d.kind(), d.offset())).collect();
+            assert_eq!(found, expected, "{input:?}");
+            // Whatever was not a problem is stripped as usual.
+            if expected.is_empty() {
+                assert_eq!(s, strip_str(input).unwrap());
+            }
+        }
+
+        let mut s = format!("[1, {too_deep} x */ /x");
+        let diagnostics = strip_comments_collect_diagnostics(&mut s, settings).unwrap();
+        let found: Vec<_> = diagnostics.iter().map(|d| (d.kind(), d.offset())).collect();
+        let deep = 4 + too_deep.len() - 1;
+        assert_eq!(
+            found,
+            [(CommentNestingTooDeep, deep), (UnterminatedBlockComment, 4)]
+        );
+        assert_eq!(s, format!("[1, {}", " ".repeat(s.len() - 4)));
+    }
+
+    #[test]
+    fn comma_before_commented_value() {
+        let cases = [
+            ("[1, /*c*/ 2]", "[1,       2]"),
+            ("{ \"a\":1, /*c*/ \"b\":2 }", "{ \"a\":1,       \"b\":2 }"),
+            (
+                "[1e5, // c\n -2.5E-3, #c\n +1,]",
+                "[1e5,     \n -2.5E-3,   \n +1 ]",
+            ),
+            ("[1, /*,*/ // ]\n ]", "[1            \n ]"),
+        ];
+        for (input, expected) in cases {
+            let mut json = String::from(input);
+            strip(&mut json).unwrap();
+            assert_eq!(json, expected, "{input:?}");
+            for chunk in 1..=4 {
+                assert_eq!(
+                    strip_chunked(input, chunk).unwrap(),
+                    expected,
+                    "chunk {chunk}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn line_comment_continuation() {
+        let settings = CommentSettings {
+            line_comment_continuation: true,
+            ..CommentSettings::default()
+        };
+        let json = "// first \\\nsecond\n{\"a\": 1}";
+        let mut stripped = String::new();
+        settings
+            .strip_comments(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap();
+        assert_eq!(stripped, "          \n      \n{\"a\": 1}");
+
+        let json = "// first \\\r\nsecond\r\n{\"a\": 1}";
+        let mut stripped = String::new();
+        settings
+            .strip_comments(json.as_bytes())
+            .read_to_string(&mut stripped)
+            .unwrap();
+        assert_eq!(stripped, "          \r\n      \r\n{\"a\": 1}");
+
+        // Disabled by default.
+        let json = "// first \\\n{\"a\": 1}";
+        assert_eq!(strip_string(json), "          \n{\"a\": 1}");
+    }
+
+    #[test]
+    fn line_comment_continuation_across_reads() {
+        let settings = CommentSettings {
+            line_comment_continuation: true,
+            ..CommentSettings::default()
+        };
+        let json = "// a \\\r\nb\n{}";
+        for split in 1..json.len() {
+            let mut state = Top;
+            let mut buf = json.as_bytes().to_vec();
+            let (first, second) = buf.split_at_mut(split);
+            strip_buf(&mut state, first, settings).unwrap();
+            strip_buf(&mut state, second, settings).unwrap();
+            assert_eq!(buf, b"      \r\n \n{}", "split at {split}");
+        }
+    }
+
+    #[test]
+    fn comma_in_string_is_not_trailing() {
+        assert_eq!(strip_string(r#"{"k":"a,"}"#), r#"{"k":"a,"}"#);
+        assert_eq!(strip_string(r#"["x,"]"#), r#"["x,"]"#);
+        assert_eq!(strip_string(r#"{"k":"a,",}"#), r#"{"k":"a," }"#);
+        assert_eq!(strip_string(r#"["\",", ]"#), r#"["\","  ]"#);
+    }
+
+    #[test]
+    fn cancel_mid_stream() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut reader = StripComments::new(r#"{"a": 1, /* comment */ "b": 2}"#.as_bytes())
+            .with_cancel(cancel.clone());
+
+        let mut buf = [0; 8];
+        // The comma is held back until it is known not to be trailing.
+        assert_eq!(reader.read(&mut buf).unwrap(), 7);
+        assert_eq!(&buf[..7], br#"{"a": 1"#);
+
+        cancel.store(true, Ordering::Relaxed);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn escapes_then_comments() {
+        // An escaped quote doesn't end the string, so the `//` is string content.
+        assert_eq!(strip_string(r#"{"a": "\"//"}"#), r#"{"a": "\"//"}"#);
+        // The byte after a backslash is never a comment start.
+        assert_eq!(strip_string(r#"{"a": "\/* "}"#), r#"{"a": "\/* "}"#);
+        assert_eq!(strip_string(r#"["\#", "\/"]"#), r#"["\#", "\/"]"#);
+        // A comment straight after a string that ends in an escape.
+        assert_eq!(strip_string(r#""\""// c"#), r#""\""    "#);
+        assert_eq!(strip_string(r#""a\\"/* c */"#), r#""a\\"       "#);
+        assert_eq!(strip_string("\"\\\\\"# c\n1"), "\"\\\\\"   \n1");
+    }
+
+    #[test]
+    fn unicode_escapes_in_strings() {
+        let json = "{\"clef\": \"\\uD834\\uDD1E\", \"sep\": \"a\u{2028}b\\u2028\"} // \u{2028}";
+        assert_eq!(
+            strip_string(json),
+            "{\"clef\": \"\\uD834\\uDD1E\", \"sep\": \"a\u{2028}b\\u2028\"}       "
+        );
+    }
+
+    #[test]
+    fn trailing_comma_positions_nested() {
+        let json = r#"[1, [2,], {"a": [3,],},]"#;
+        assert_eq!(trailing_comma_positions(json).unwrap(), vec![6, 18, 20, 22]);
+
+        let json = "[\"x,]\", 1, // ,]\n /* , */ ]";
+        assert_eq!(trailing_comma_positions(json).unwrap(), vec![9]);
+
+        assert!(trailing_comma_positions("[1, 2]").unwrap().is_empty());
+    }
+
+    const STATES: [State; 25] = [
+        Top,
+        InString,
+        StringEscape,
+        InBacktickString,
+        BacktickEscape,
+        InSingleQuoteString,
+        SingleQuoteEscape,
+        InComment,
+        InBlockComment,
+        MaybeCommentEnd,
+        InNestedComment(1),
+        InNestedComment(2),
+        MaybeNestedCommentEnd(1),
+        MaybeNestedCommentEnd(2),
+        MaybeNestedCommentStart(1),
+        MaybeNestedCommentStart(MAX_COMMENT_NESTING),
+        MaybePreservedComment(0),
+        MaybePreservedComment(1),
+        MaybePreservedComment(2),
+        InPreservedComment,
+        MaybePreservedCommentEnd,
+        InLineComment,
+        LineCommentContinuation,
+        MaybeLineSeparator(1),
+        MaybeLineSeparator(2),
+    ];
+
+    fn all_settings() -> [CommentSettings; 15] {
+        [
+            CommentSettings::all(),
+            CommentSettings::hash_only(),
+            CommentSettings::c_style(),
+            CommentSettings {
+                line_comment_continuation: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                forbid_control_chars_in_strings: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                backtick_strings: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                single_quote_strings: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                nested_block_comments: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                unicode_line_separators: true,
+                line_comment_continuation: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                skip_bom: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                scan_strategy: ScanStrategy::Table,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                string_delimiter: b'\'',
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                lone_slashes: true,
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                preserve_comment_prefix: CommentPrefix::new(b"*!"),
+                ..CommentSettings::all()
+            },
+            CommentSettings {
+                preserve_comment_prefix: CommentPrefix::new(b""),
+                ..CommentSettings::all()
+            },
+        ]
+    }
+
+    #[test]
+    fn transition_table() {
+        let settings = CommentSettings::all();
+        let t = |state, c| transition(state, c, settings).unwrap();
+        assert_eq!(t(Top, b'"'), (InString, Keep));
+        assert_eq!(t(Top, b'/'), (InComment, Blank));
+        assert_eq!(t(Top, b'#'), (InLineComment, Blank));
+        assert_eq!(t(Top, b','), (Top, Keep));
+        assert_eq!(t(InString, b'\\'), (StringEscape, Keep));
+        assert_eq!(t(StringEscape, b'"'), (InString, Keep));
+        assert_eq!(t(InComment, b'*'), (InBlockComment, Blank));
+        assert_eq!(t(InComment, b'/'), (InLineComment, Blank));
+        assert_eq!(t(MaybeCommentEnd, b'/'), (Top, Blank));
+        assert_eq!(t(MaybeCommentEnd, b'*'), (MaybeCommentEnd, Blank));
+        assert_eq!(t(InLineComment, b'\n'), (Top, Keep));
+        assert_eq!(t(InLineComment, b'\\'), (InLineComment, Blank));
+        assert!(transition(InComment, b'x', settings).is_err());
+        assert_eq!(
+            transition(Top, b'#', CommentSettings::c_style()).unwrap(),
+            (Top, Keep)
+        );
+    }
+
+    // The bulk paths in `step` must match `transition` for every state and byte.
+    #[test]
+    fn step_matches_transition() {
+        for settings in all_settings() {
+            for state in STATES {
+                for c in 0..=u8::MAX {
+                    let mut buf = [c];
+                    let stepped = step(state, &mut buf, &mut 0, settings, &mut ()).ok();
+                    let expected = transition(state, c, settings).ok();
+                    assert_eq!(
+                        stepped.map(|s| (s, buf[0])),
+                        expected.map(|(s, action)| (s, if action == Blank { b' ' } else { c })),
+                        "{state:?} {c}"
+                    );
+                }
+            }
+        }
+    }
+
+    // Drives `transition` one byte at a time and compares with `strip_buf`.
+    #[test]
+    fn strip_buf_matches_transition() {
+        let inputs = [
+            "{/* a ** b */\"x\": \"/*\\\"//\", // c \\\r\n # d \\\n e\n}",
+            "[1 /*/ x */, 2] // \\ \r\r\n # \\\\\n",
+            "/** C **/ { \"foo\": 123 } // end",
+            "// a\u{2028}1 # \u{2029}\\\u{2028}2 // \u{20ac}\u{2028}\u{e2}\u{2029}",
+        ];
+        for settings in all_settings() {
+            // `transition` alone can't blank a `/` kept with `lone_slashes` once a comment
+            // starts after it, or put back the opening of a comment kept with
+            // `preserve_comment_prefix`.
+            let settings = CommentSettings {
+                trailing_commas: false,
+                elide_empty_elements: false,
+                lone_slashes: false,
+                preserve_comment_prefix: None,
+                ..settings
+            };
+            for input in inputs {
+                let mut state = Top;
+                let mut expected = input.as_bytes().to_vec();
+                let expected = expected
+                    .iter_mut()
+                    .try_for_each(|c| {
+                        let (new_state, action) = transition(state, *c, settings)?;
+                        state = new_state;
+                        if action == Blank {
+                            *c = b' ';
+                        }
+                        Ok::<_, StripErrorKind>(())
+                    })
+                    .map(|()| (expected, state));
+
+                let mut state = Top;
+                let mut buf = input.as_bytes().to_vec();
+                let actual = strip_buf(&mut state, &mut buf, settings).map(|()| (buf, state));
+                assert_eq!(actual.ok(), expected.ok(), "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn strip_any_input() {
+        let expected = "{\"a\": 1     }";
+        let input = "{\"a\": 1,/**/}";
+        assert_eq!(strip_str(input).unwrap(), expected);
+        assert_eq!(strip_str(String::from(input)).unwrap(), expected);
+        assert_eq!(
+            strip_str(std::borrow::Cow::Borrowed(input)).unwrap(),
+            expected
+        );
+
+        let expected = expected.as_bytes();
+        assert_eq!(strip_bytes(input.as_bytes()).unwrap(), expected);
+        assert_eq!(strip_bytes(Vec::from(input)).unwrap(), expected);
+        assert_eq!(strip_bytes(b"{\"a\": 1,/**/}").unwrap(), expected);
+
+        strip_str("[1, /x]").unwrap_err();
+        strip_bytes("[1, /x]").unwrap_err();
+
+        let mut out = b"[0] ".to_vec();
+        strip_into(input.as_bytes(), &mut out).unwrap();
+        assert_eq!(out, [b"[0] ", expected].concat());
+        let err = strip_into(b"[1, /x]", &mut out).unwrap_err();
+        assert_eq!(strip_error(&err).offset(), 5);
+        assert_eq!(out, [b"[0] ", expected].concat());
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn integrity() {
+        use sha2::{Digest, Sha256};
+
+        let input = "{\"a\": [1, 2]} // config";
+        let expected: [u8; 32] = Sha256::digest(input).into();
+
+        let mut out = String::new();
+        let chunked = Chunked {
+            bytes: input.as_bytes(),
+            chunk: 3,
+        };
+        StripComments::new(chunked)
+            .with_integrity(expected)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "{\"a\": [1, 2]}          ");
+
+        // A single modified byte is only noticed at the end of the input.
+        let tampered = input.replace('2', "3");
+        let mut reader = StripComments::new(tampered.as_bytes()).with_integrity(expected);
+        let mut buf = vec![0; tampered.len()];
+        assert_eq!(reader.read(&mut buf).unwrap(), tampered.len());
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+
+        // The check combines with other settings and limits.
+        let mut out = String::new();
+        let settings = CommentSettings {
+            fill: b'_',
+            ..CommentSettings::all()
+        };
+        settings
+            .strip_comments(input.as_bytes())
+            .with_integrity(expected)
+            .with_max_bytes(input.len())
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "{\"a\": [1, 2]} _________");
+    }
+
+    #[test]
+    fn control_chars_in_strings() {
+        let strict = CommentSettings {
+            forbid_control_chars_in_strings: true,
+            ..CommentSettings::all()
+        };
+        for (input, message) in [
+            (
+                "{\"a\": \"line\nbreak\"}",
+                "raw control character in string at offset 11",
+            ),
+            ("[\"cr\r\"]", "raw control character in string at offset 4"),
+            (
+                "/* \t */ \"\t\"",
+                "raw control character in string at offset 9",
+            ),
+            // Escaping a control character doesn't make it valid.
+            (
+                "[\"a\\\nb\"]",
+                "raw control character in string at offset 4",
+            ),
+        ] {
+            let mut s = String::from(input);
+            strip_comments_in_place(&mut s.clone(), CommentSettings::all()).unwrap();
+            let err = strip_comments_in_place(&mut s, strict).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), message);
+
+            let mut reader = strict.strip_comments(input.as_bytes());
+            reader.read_to_string(&mut String::new()).unwrap_err();
+        }
+
+        // Escape sequences for control characters and ones outside strings are fine.
+        let mut s = String::from("{\n\t\"a\": \"\\n\\t\\r\" // \t\r\n}");
+        strip_comments_in_place(&mut s, strict).unwrap();
+
+        let single = CommentSettings {
+            single_quote_strings: true,
+            ..strict
+        };
+        let err = strip_comments_in_place(&mut String::from("['\\\t']"), single).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::ControlCharInString, 3)
+        );
+    }
+
+    #[test]
+    fn stats_count_comment_at_eof() {
+        let input = "{\"a\": 1} // x";
+        let mut s = String::from(input);
+        let stats = strip_comments_in_place_with_stats(&mut s, CommentSettings::all()).unwrap();
+        assert_eq!(
+            stats,
+            StripStats {
+                comments: 1,
+                line_comments: 1,
+                bytes_blanked: 4,
+                ..StripStats::default()
+            }
+        );
+        assert!(stats.modified());
+
+        for chunk in [1, 3, input.len()] {
+            let mut reader = StripComments::new(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut String::new()).unwrap();
+            assert_eq!(reader.stats(), stats, "chunk {chunk}");
+        }
+
+        let mut s = String::from("{\"a\": \"// not a comment\"}");
+        let stats = strip_comments_in_place_with_stats(&mut s, CommentSettings::all()).unwrap();
+        assert!(!stats.modified());
+    }
+
+    #[test]
+    fn stats_by_kind() {
+        let input = "{\"a\": [1, 2,], /* b */ \"c\": 3, // d\n \"e\": 4, # f\n}";
+        let expected = StripStats {
+            comments: 3,
+            block_comments: 1,
+            line_comments: 1,
+            hash_comments: 1,
+            commas: 2,
+            bytes_blanked: 16,
+        };
+        let mut s = String::from(input);
+        let stats = strip_comments_in_place_with_stats(&mut s, CommentSettings::all()).unwrap();
+        assert_eq!(stats, expected);
+        assert_eq!(
+            stats.bytes_blanked,
+            s.bytes().filter(|&c| c == b' ').count() - 9
+        );
+
+        for chunk in 1..=input.len() {
+            let mut reader = StripComments::new(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut String::new()).unwrap();
+            assert_eq!(reader.stats(), expected, "chunk {chunk}");
+        }
+
+        let settings = CommentSettings {
+            nested_block_comments: true,
+            strip_leading_shebang: true,
+            ..CommentSettings::c_style()
+        };
+        let mut s = String::from("#!x\n/* /* */ */ [1] //");
+        let stats = strip_comments_in_place_with_stats(&mut s, settings).unwrap();
+        assert_eq!(
+            (
+                stats.block_comments,
+                stats.line_comments,
+                stats.hash_comments
+            ),
+            (1, 1, 1)
+        );
+        assert_eq!(stats.bytes_blanked, 3 + 11 + 2);
+    }
+
+    #[test]
+    fn reporting_changes() {
+        let settings = CommentSettings::all();
+        for (input, modified) in [
+            ("", false),
+            ("{\"a\": [1, 2]}", false),
+            ("[\"/* , ] */\", \"#\"]", false),
+            ("[1,]", true),
+            ("/**/1", true),
+            ("1 #", true),
+        ] {
+            let mut s = String::from(input);
+            let reported = strip_comments_in_place_reporting(&mut s, settings).unwrap();
+            assert_eq!(reported, modified, "{input:?}");
+            assert_eq!(reported, s != input, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        let input = "[1, /* a /* b */ \"c\" */ 2]";
+        // By default the comment ends at the first `*/`, leaving a stray `/`.
+        let err = strip(&mut String::from(input)).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::InvalidCommentStart, 23)
+        );
+
+        let settings = CommentSettings {
+            nested_block_comments: true,
+            ..CommentSettings::all()
+        };
+        let expected = "[1,                     2]";
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let mut reader = settings.strip_comments(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut out).unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+            assert_eq!(reader.stats().comments, 1);
+        }
+
+        for (input, expected) in [
+            ("/*/ x */1", "        1"),
+            ("/* //* x */ */1", "              1"),
+            ("/* /**/ **/1", "           1"),
+            ("/*/**/*/[1,]", "        [1 ]"),
+        ] {
+            let mut s = String::from(input);
+            strip_comments_in_place(&mut s, settings).unwrap();
+            assert_eq!(s, expected, "{input:?}");
+        }
+
+        let mut reader = settings.strip_comments("/* /* */".as_bytes());
+        let err = reader.read_to_string(&mut String::new()).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::UnterminatedBlockComment, 0)
+        );
+
+        let too_deep = "/*".repeat(usize::from(MAX_COMMENT_NESTING) + 1);
+        let err = strip_comments_in_place(&mut too_deep.clone(), settings).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::CommentNestingTooDeep, too_deep.len() - 1)
+        );
+        let deepest = "/*".repeat(usize::from(MAX_COMMENT_NESTING));
+        let mut s = deepest.clone() + &"*/".repeat(usize::from(MAX_COMMENT_NESTING));
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert!(s.bytes().all(|c| c == b' '));
+    }
+
+    #[test]
+    fn preserve_comment_prefix() {
+        let settings = CommentSettings {
+            preserve_comment_prefix: CommentPrefix::new(b"*!"),
+            ..CommentSettings::all()
+        };
+        let input = "/**! (c) */ [1, /**/ 2, /*!*/ 3, /**!*/] /**! [ */ // x";
+        let expected = "/**! (c) */ [1,      2,       3  /**!*/] /**! [ */     ";
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let mut reader = settings.strip_comments(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut out).unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+            assert_eq!((reader.stats().comments, reader.stats().commas), (3, 1));
+        }
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, expected);
+
+        // With lone slashes, the `/` is held back whatever comes after it.
+        let settings = CommentSettings {
+            lone_slashes: true,
+            preserve_comment_prefix: CommentPrefix::new(b"!"),
+            ..CommentSettings::all()
+        };
+        let input = "{a/b: 1, /*! c */ d: [2 /* e */,]}";
+        let expected = "{a/b: 1, /*! c */ d: [2         ]}";
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let mut reader = settings.strip_comments(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut out).unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+        }
+
+        // An empty prefix keeps every block comment, and a kept comment must still end.
+        let settings = CommentSettings {
+            preserve_comment_prefix: CommentPrefix::new(b""),
+            ..CommentSettings::all()
+        };
+        let mut s = String::from("[/**/ 1 /* a */] # b");
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, "[/**/ 1 /* a */]    ");
+        let mut reader = settings.strip_comments("[1] /*! open".as_bytes());
+        let err = reader.read_to_string(&mut String::new()).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::UnterminatedBlockComment, 4)
+        );
+
+        // Nested comments are never kept.
+        let settings = CommentSettings {
+            nested_block_comments: true,
+            preserve_comment_prefix: CommentPrefix::new(b"!"),
+            ..CommentSettings::all()
+        };
+        let mut s = String::from("/*! /* */ */1");
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, "            1");
+    }
+
+    #[test]
+    fn unicode_line_separators() {
+        let settings = CommentSettings {
+            unicode_line_separators: true,
+            ..CommentSettings::all()
+        };
+        let input = "{\"a\": 1, // one\u{2028}\"b\": \"// \u{2029}\", # \u{20ac}\u{2029}\"c\": 3}";
+        let expected = "{\"a\": 1,          \"b\": \"// \u{2029}\",         \"c\": 3}";
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let mut reader = settings.strip_comments(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut out).unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+            assert_eq!(reader.stats().comments, 2);
+        }
+
+        // Off by default, where the comment runs on to the end of the line.
+        let mut s = String::from("[1] // x\u{2028}[2]");
+        strip(&mut s).unwrap();
+        assert_eq!(s, format!("[1]{}", " ".repeat(11)));
+    }
+
+    #[test]
+    fn backtick_strings() {
+        let settings = CommentSettings {
+            backtick_strings: true,
+            ..CommentSettings::all()
+        };
+        let input = "{\"a\": `// kept /* too */ \\` # and`, // gone\n\"b\": `${x /* kept */}`}";
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(
+            s,
+            "{\"a\": `// kept /* too */ \\` # and`,        \n\"b\": `${x /* kept */}`}"
+        );
+
+        // Off by default, where a backtick is ordinary text.
+        let mut s = String::from("[`a`, // b`\n]");
+        strip(&mut s).unwrap();
+        assert_eq!(s, "[`a`       \n]");
+
+        let mut reader = settings.strip_comments("[`open // x".as_bytes());
+        reader.read_to_string(&mut String::new()).unwrap_err();
+    }
+
+    #[test]
+    fn single_quote_strings() {
+        let settings = CommentSettings {
+            single_quote_strings: true,
+            ..CommentSettings::all()
+        };
+        let input = "{'a': 'b // \\' \" # /* c', \"it's\": 1, // gone\n'd': ['e',]}";
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(
+            s,
+            "{'a': 'b // \\' \" # /* c', \"it's\": 1,        \n'd': ['e' ]}"
+        );
+
+        // Off by default, where a single quote is ordinary text.
+        let mut s = String::from("['a // b']");
+        strip(&mut s).unwrap();
+        assert_eq!(s, "['a       ");
+
+        let mut reader = settings.strip_comments("['open // x".as_bytes());
+        let err = reader.read_to_string(&mut String::new()).unwrap_err();
+        assert_eq!(strip_error(&err).offset(), 1);
+    }
+
+    #[test]
+    fn string_delimiter() {
+        let input = "[`a // b \\` # c`, \"d // e\"] # f\n";
+        let expected = "[`a // b \\` # c`, \"d           \n";
+        for scan_strategy in [ScanStrategy::Branch, ScanStrategy::Table] {
+            let settings = CommentSettings {
+                string_delimiter: b'`',
+                scan_strategy,
+                ..CommentSettings::all()
+            };
+            let mut s = String::from(input);
+            strip_comments_in_place(&mut s, settings).unwrap();
+            assert_eq!(s, expected);
+            for chunk in 1..=4 {
+                let mut out = String::new();
+                StripComments::with_settings(
+                    settings,
+                    Chunked {
+                        bytes: input.as_bytes(),
+                        chunk,
+                    },
+                )
+                .read_to_string(&mut out)
+                .unwrap();
+                assert_eq!(out, expected, "chunks of {chunk}");
+            }
+
+            let mut reader = settings.strip_comments("[1, `open // x".as_bytes());
+            let err = reader.read_to_string(&mut String::new()).unwrap_err();
+            assert_eq!(
+                strip_error(&err),
+                StripError::new(StripErrorKind::UnterminatedString, 4)
+            );
+        }
+    }
+
+    #[test]
+    fn mixed_quote_types() {
+        let settings = CommentSettings {
+            backtick_strings: true,
+            ..CommentSettings::all()
+        };
+        // Each kind of quote is only content inside a string opened by another kind.
+        let cases = [
+            "[\"it's a // test\"]",
+            "[`say \"hi\" /* x */`]",
+            "[\"a ` // b\"]",
+            "[\"a\"`\" // b`\"c /* d\"]",
+            "[`a\\``\"`//`\"]",
+        ];
+        for input in cases {
+            let mut s = String::from(input);
+            strip_comments_in_place(&mut s, settings).unwrap();
+            assert_eq!(s, input);
+        }
+
+        let mut s = String::from("[\"'\", /* a */ `\"`, // b\n\"`\"]");
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, "[\"'\",         `\"`,     \n\"`\"]");
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        let settings = CommentSettings {
+            skip_bom: true,
+            ..CommentSettings::all()
+        };
+
+        // At the start, the mark is blanked.
+        let mut s = String::from("\u{FEFF}{\"a\": 1} // x");
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, "   {\"a\": 1}     ");
+        let mut out = String::new();
+        let chunked = Chunked {
+            bytes: "\u{FEFF}[1]".as_bytes(),
+            chunk: 1,
+        };
+        settings
+            .strip_comments(chunked)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "   [1]");
+        for len in [1, 2] {
+            let mut reader = settings.strip_comments("\u{FEFF}[1,]".as_bytes());
+            let mut out = Vec::new();
+            let mut buf = [0; 2];
+            loop {
+                match reader.read(&mut buf[..len]).unwrap() {
+                    0 => break,
+                    n => out.extend_from_slice(&buf[..n]),
+                }
+            }
+            assert_eq!(out, b"   [1 ]", "reads of {len}");
+        }
+
+        // Inside a string, raw or escaped, it is kept.
+        let input = "{\"a\": \"\u{FEFF}\", \"b\": \"\\uFEFF\"}";
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, input);
+
+        // Anywhere else, it is left for the parser to reject.
+        let input = "[1,\u{FEFF}2]";
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, input);
+
+        // Off by default.
+        let mut s = String::from("\u{FEFF}[1]");
+        strip(&mut s).unwrap();
+        assert_eq!(s, "\u{FEFF}[1]");
+    }
+
+    #[test]
+    fn leading_shebang() {
+        let settings = CommentSettings {
+            strip_leading_shebang: true,
+            ..CommentSettings::c_style()
+        };
+        let input = "#!/usr/bin/env node\n{\"a\": \"#!\"} #!";
+        let expected = format!("{}\n{{\"a\": \"#!\"}} #!", " ".repeat(19));
+        let mut s = String::from(input);
+        let stats = strip_comments_in_place_with_stats(&mut s, settings).unwrap();
+        assert_eq!(s, expected);
+        assert_eq!(stats.comments, 1);
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let chunked = Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            };
+            settings
+                .strip_comments(chunked)
+                .read_to_string(&mut out)
+                .unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+        }
+        let mut reader = settings.strip_comments("#!x\n1".as_bytes());
+        let mut out = Vec::new();
+        let mut buf = [0];
+        while let Ok(1) = reader.read(&mut buf) {
+            out.push(buf[0]);
+        }
+        assert_eq!(out, b"   \n1");
+
+        // Not after anything else, even a byte order mark.
+        for input in [" #!x\n1", "\u{FEFF}#!x\n1", "#x\n1"] {
+            let mut s = String::from(input);
+            strip_comments_in_place(&mut s, settings).unwrap();
+            assert_eq!(s, input);
+        }
+
+        // Off by default.
+        let mut s = String::from("#!x\n1");
+        strip_comments_in_place(&mut s, CommentSettings::c_style()).unwrap();
+        assert_eq!(s, "#!x\n1");
+    }
+
+    #[test]
+    fn byte_order_mark_parses() {
+        let input = "\u{FEFF}{\n  // From a Windows editor\n  \"a\": [1, 2,],\n}";
+        serde_json::from_str::<serde_json::Value>(&strip_str(input).unwrap()).unwrap_err();
+
+        let settings = CommentSettings {
+            skip_bom: true,
+            ..CommentSettings::all()
+        };
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&s).unwrap();
+        assert_eq!(value, serde_json::json!({"a": [1, 2]}));
+
+        let reader = settings.strip_comments(input.as_bytes());
+        let value: serde_json::Value = serde_json::from_reader(reader).unwrap();
+        assert_eq!(value, serde_json::json!({"a": [1, 2]}));
+    }
+
+    #[test]
+    fn carriage_return_line_endings() {
+        // Classic Mac line endings.
+        assert_eq!(strip_string("{// x\r\"a\":1}"), "{    \r\"a\":1}");
+        assert_eq!(strip_string("[1, # x\r]"), "[1     \r]");
+
+        // A `\r\n` split between reads ends the comment once.
+        let input = "{// x\r\n\"a\": 1, # y\r\n}";
+        let expected = "{    \r\n\"a\": 1     \r\n}";
+        for chunk in 1..=input.len() {
+            let mut out = String::new();
+            let mut reader = StripComments::new(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            reader.read_to_string(&mut out).unwrap();
+            assert_eq!(out, expected, "chunks of {chunk}");
+            assert_eq!(reader.stats().comments, 2);
+        }
+
+        // A continuation still carries on over a lone `\r`.
+        let settings = CommentSettings {
+            line_comment_continuation: true,
+            ..CommentSettings::all()
+        };
+        let mut s = String::from("// a \\\rb\r1");
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, "      \r \r1");
+    }
+
+    #[test]
+    fn non_utf8_bytes() {
+        let input: &[u8] = b"{\"\xe9\x80\xff\": \"\x93q\x94\", // \xe9\xff\r\n # \x80\n /* \xfe\xff */ \"b\": [1,],}";
+        let expected: &[u8] =
+            b"{\"\xe9\x80\xff\": \"\x93q\x94\",      \r\n    \n          \"b\": [1 ] }";
+        let mut buf = input.to_vec();
+        strip_bytes_latin1(&mut buf).unwrap();
+        assert_eq!(buf, expected);
+        assert_eq!(strip_bytes(input).unwrap(), expected);
+
+        let mut out = Vec::new();
+        StripComments::new(input).read_to_end(&mut out).unwrap();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn max_bytes() {
+        let input = "[1, 2, 3] // 16";
+        for chunk in [1, 4, 64] {
+            let reader = |limit| {
+                StripComments::new(Chunked {
+                    bytes: input.as_bytes(),
+                    chunk,
+                })
+                .with_max_bytes(limit)
+            };
+            let mut out = String::new();
+            reader(input.len()).read_to_string(&mut out).unwrap();
+            assert_eq!(out, "[1, 2, 3]      ");
+
+            let mut out = Vec::new();
+            let err = reader(input.len() - 1).read_to_end(&mut out).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            assert!(out.len() < input.len());
+        }
+
+        // Reads past the limit are never made.
+        let mut reader = StripComments::new(input.as_bytes()).with_max_bytes(4);
+        let mut buf = [0; 64];
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert_eq!(reader.inner.len(), input.len() - 5);
+
+        // The limit combines with cancellation and with any settings.
+        let cancel = Arc::new(AtomicBool::new(false));
+        let reader = || {
+            CommentSettings::hash_only()
+                .strip_comments("[1] # 9 // x".as_bytes())
+                .with_max_bytes(12)
+                .with_cancel(cancel.clone())
+        };
+        let mut out = String::new();
+        reader().read_to_string(&mut out).unwrap();
+        assert_eq!(out, "[1]         ");
+        let mut too_long = reader().with_max_bytes(11);
+        assert_eq!(
+            too_long.read_to_end(&mut Vec::new()).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(
+            reader().read_to_end(&mut Vec::new()).unwrap_err().kind(),
+            ErrorKind::Other
+        );
+    }
+
+    fn strip_error(err: &Error) -> StripError {
+        *err.get_ref().unwrap().downcast_ref::<StripError>().unwrap()
+    }
+
+    #[test]
+    fn typed_errors() {
+        use StripErrorKind::*;
+
+        for (input, kind, offset) in [
+            ("{\"a\": 1} /* open", UnterminatedBlockComment, 9),
+            ("[1, /* open *", UnterminatedBlockComment, 4),
+            ("[\"open", UnterminatedString, 1),
+            ("[\"open\\", UnterminatedString, 1),
+            ("[1, /x]", InvalidCommentStart, 5),
+            ("[1] /", InvalidCommentStart, 5),
+        ] {
+            for chunk in [1, 2, input.len()] {
+                let mut reader = StripComments::new(Chunked {
+                    bytes: input.as_bytes(),
+                    chunk,
+                });
+                let err = reader.read_to_string(&mut String::new()).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::InvalidData);
+                assert_eq!(
+                    strip_error(&err),
+                    StripError::new(kind, offset),
+                    "{input:?} in chunks of {chunk}"
+                );
+            }
+        }
+
+        let err = strip(&mut String::from("[1, /x]")).unwrap_err();
+        assert_eq!(strip_error(&err), StripError::new(InvalidCommentStart, 5));
+        assert_eq!(err.to_string(), "invalid comment start at offset 5");
+    }
+
+    #[test]
+    fn top_level_and_complete() {
+        for (input, top_level, complete) in [
+            ("[1, 2]", true, true),
+            ("[1, 2,", true, true),
+            ("[1] // a", false, true),
+            ("[1] // a \\", false, true),
+            ("[1] /", false, false),
+            ("[1] /* a", false, false),
+            ("[\"a", false, false),
+        ] {
+            let settings = CommentSettings {
+                line_comment_continuation: true,
+                ..CommentSettings::all()
+            };
+            let mut reader = StripComments::with_settings(settings, input.as_bytes());
+            assert!(reader.is_at_top_level() && reader.is_complete());
+            let _ = reader.read_to_end(&mut Vec::new());
+            assert_eq!(reader.is_at_top_level(), top_level, "{input:?}");
+            assert_eq!(reader.is_complete(), complete, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn recover_unterminated_strings() {
+        let settings = CommentSettings {
+            recover_unterminated_strings: true,
+            ..CommentSettings::all()
+        };
+        for (input, expected, offset) in [
+            (
+                "[1] // x\n[\"a\", \"b // c",
+                "[1]     \n[\"a\", \"b // c",
+                15,
+            ),
+            ("{\"a\": \"b\\", "{\"a\": \"b\\", 6),
+            ("[1,\"", "[1,\"", 3),
+        ] {
+            for chunk in [1, 2, input.len()] {
+                let mut reader = StripComments::with_settings(
+                    settings,
+                    Chunked {
+                        bytes: input.as_bytes(),
+                        chunk,
+                    },
+                );
+                let mut out = String::new();
+                reader.read_to_string(&mut out).unwrap();
+                assert_eq!(out, expected, "{input:?} in chunks of {chunk}");
+                assert_eq!(
+                    reader.unterminated_string(),
+                    Some(StripError::new(StripErrorKind::UnterminatedString, offset))
+                );
+            }
+        }
+
+        // Complete input and unterminated comments are unaffected.
+        let mut reader = StripComments::with_settings(settings, "[\"a\"]".as_bytes());
+        reader.read_to_string(&mut String::new()).unwrap();
+        assert_eq!(reader.unterminated_string(), None);
+        let mut reader = StripComments::with_settings(settings, "[\"a\" /* b".as_bytes());
+        let err = reader.read_to_string(&mut String::new()).unwrap_err();
+        assert_eq!(
+            strip_error(&err).kind(),
+            StripErrorKind::UnterminatedBlockComment
+        );
+        assert_eq!(reader.unterminated_string(), None);
+    }
+
+    #[test]
+    fn error_line_column() {
+        use StripErrorKind::*;
+
+        for (input, position) in [
+            ("[1, /x]", (1, 6)),
+            ("{\n  \"a\": 1\n} /* open", (3, 3)),
+            ("{\r\n\"é\": \"ü\", \"open", (2, 11)),
+            ("[1]\n/", (2, 2)),
+            ("[1]\r\r/", (3, 2)),
+            ("// a\r[1] /x", (2, 6)),
+        ] {
+            let mut reader = StripComments::new(input.as_bytes());
+            let err = reader.read_to_string(&mut String::new()).unwrap_err();
+            assert_eq!(
+                strip_error(&err).line_column(input.as_bytes()),
+                position,
+                "{input:?}"
+            );
+        }
+        // An offset past the end of `input` stops at its end.
+        let err = StripError::new(UnterminatedString, 10);
+        assert_eq!(err.line_column(b"\"ab\ncd"), (2, 3));
+    }
+
+    #[test]
+    fn best_effort() {
+        use StripErrorKind::*;
+        let mut s = String::from("[1, /* a */ 2] /* unterminated\n \"x\"");
+        let (len, err) = strip_best_effort(&mut s);
+        assert_eq!(len, 15);
+        assert_eq!(err, Some(StripError::new(UnterminatedBlockComment, 15)));
+        assert_eq!(s, "[1,         2] /* unterminated\n \"x\"");
+
+        let mut s = String::from("[\"ok\", \"open");
+        let (len, err) = strip_best_effort(&mut s);
+        assert_eq!(
+            (len, err),
+            (7, Some(StripError::new(UnterminatedString, 7)))
+        );
+
+        let mut s = String::from("[1, /x]");
+        let (len, err) = strip_best_effort(&mut s);
+        assert_eq!(
+            (len, err),
+            (4, Some(StripError::new(InvalidCommentStart, 5)))
+        );
+
+        let mut s = String::from("[1] /");
+        let (len, err) = strip_best_effort(&mut s);
+        assert_eq!(
+            (len, err),
+            (4, Some(StripError::new(InvalidCommentStart, 5)))
+        );
+
+        let mut s = String::from("[1, 2,] // done");
+        let (len, err) = strip_best_effort(&mut s);
+        assert_eq!((len, err.is_none()), (15, true));
+        assert_eq!(s, "[1, 2 ]        ");
     }
-}
 
-fn in_comment(c: &mut u8, settings: CommentSettings) -> Result<State> {
-    let new_state = match c {
-        b'*' if settings.block_comments => InBlockComment,
-        b'/' if settings.slash_line_comments => InLineComment,
-        _ => return Err(ErrorKind::InvalidData.into()),
-    };
-    *c = b' ';
-    Ok(new_state)
+    #[test]
+    fn documents() {
+        let input = r#"// first
+{"a": 1,}
+---
+{
+  "b": "
+---
+", /* not a
+---
+separator */
 }
+---"#;
+        let docs = strip_documents(input, "---").unwrap();
+        assert_eq!(docs.len(), 2);
+        assert_eq!(docs[0], "        \n{\"a\": 1 }\n");
+        assert_eq!(
+            docs[1].replace(|c: char| c.is_ascii_whitespace(), ""),
+            "{\"b\":\"---\"}"
+        );
 
-fn maybe_comment_end(c: &mut u8) -> State {
-    let old = *c;
-    *c = b' ';
-    match old {
-        b'/' => Top,
-        b'*' => MaybeCommentEnd,
-        _ => InBlockComment,
+        let docs = strip_documents("1\r\n---\r\n2", "---").unwrap();
+        assert_eq!(docs, ["1\r\n", "2"]);
+        let docs = strip_documents("1\r---\r2 // c\r---", "---").unwrap();
+        assert_eq!(docs, ["1\r", "2     \r"]);
+        assert_eq!(strip_documents("1 ---\n2", "---").unwrap(), ["1 ---\n2"]);
+        assert_eq!(strip_documents("1\n---\n", "---").unwrap(), ["1\n"]);
+        assert_eq!(strip_documents("---\n1", "---").unwrap(), ["", "1"]);
+        assert_eq!(strip_documents("", "---").unwrap(), [""]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::{ErrorKind, Read};
+    #[test]
+    fn trailing_commas_across_reads() {
+        let inputs = [
+            "[1,]",
+            "{\"a\": [1, 2,\n  ],\n  \"b\": {\"c\": 3,},}",
+            "[1, /* a */ // b\n # c\n ]",
+            "[false,/*x*/]",
+            "{\"a\": 1, #x\n }",
+            "[1,,]",
+            "[[1,],[2, ], 3 ,\n]",
+            "[\",\", \"]\",]",
+            "{\"a\": 1,\n\"b\": 2}",
+            "[1, 2, // x\n 3]",
+            "[1,",
+        ];
+        for input in inputs {
+            let mut expected = String::from(input);
+            strip(&mut expected).unwrap();
+            for chunk in 1..=4 {
+                // Both the inner reads and the caller's buffer are tiny.
+                let mut reader = StripComments::new(Chunked {
+                    bytes: input.as_bytes(),
+                    chunk,
+                });
+                let mut out = Vec::new();
+                let mut buf = [0; 4];
+                loop {
+                    match reader.read(&mut buf).unwrap() {
+                        0 => break,
+                        n => out.extend_from_slice(&buf[..n]),
+                    }
+                }
+                assert_eq!(String::from_utf8(out).unwrap(), expected, "chunk {chunk}");
+                assert_eq!(strip_chunked(input, chunk).unwrap(), expected);
+            }
+        }
 
-    fn strip_string(input: &str) -> String {
         let mut out = String::new();
-        let count = StripComments::new(input.as_bytes())
+        StripComments::new(Chunked {
+            bytes: b"{\"a\": [1, 2,],}",
+            chunk: 4,
+        })
+        .read_to_string(&mut out)
+        .unwrap();
+        serde_json::from_str::<serde_json::Value>(&out).unwrap();
+
+        // A comma held at a read boundary still counts once.
+        let mut reader = StripComments::new(Chunked {
+            bytes: b"[1,\n]",
+            chunk: 1,
+        });
+        reader.read_to_string(&mut String::new()).unwrap();
+        assert_eq!(reader.stats().commas, 1);
+    }
+
+    #[test]
+    fn empty_elements() {
+        let elide = CommentSettings {
+            elide_empty_elements: true,
+            ..CommentSettings::all()
+        };
+        let cases = [
+            ("[1,,2]", "[1,,2]", "[1, 2]"),
+            ("[,1]", "[,1]", "[ 1]"),
+            ("[1,,]", "[1, ]", "[1  ]"),
+            ("[1, ,/**/,]", "[1, ,     ]", "[1        ]"),
+            ("[,]", "[ ]", "[ ]"),
+            (
+                "{,\"a\": [1, [,2]],,}",
+                "{,\"a\": [1, [,2]], }",
+                "{ \"a\": [1, [ 2]]  }",
+            ),
+            ("[\",,\", 1]", "[\",,\", 1]", "[\",,\", 1]"),
+        ];
+        for (input, default, elided) in cases {
+            for (settings, expected) in [(CommentSettings::all(), default), (elide, elided)] {
+                let mut json = String::from(input);
+                strip_comments_in_place(&mut json, settings).unwrap();
+                assert_eq!(json, expected, "{input:?}");
+                for chunk in 1..=4 {
+                    let mut out = String::new();
+                    StripComments::with_settings(
+                        settings,
+                        Chunked {
+                            bytes: input.as_bytes(),
+                            chunk,
+                        },
+                    )
+                    .read_to_string(&mut out)
+                    .unwrap();
+                    assert_eq!(out, expected, "{input:?} in chunks of {chunk}");
+                }
+            }
+        }
+
+        let mut reader = StripCommentsBuilder::new()
+            .elide_empty_elements(true)
+            .build("[,,1,,]".as_bytes());
+        reader.read_to_string(&mut String::new()).unwrap();
+        assert_eq!(reader.stats().commas, 4);
+    }
+
+    #[test]
+    fn builder_sets_every_setting() {
+        let settings = CommentSettings {
+            block_comments: false,
+            slash_line_comments: false,
+            hash_line_comments: false,
+            trailing_commas: false,
+            elide_empty_elements: true,
+            line_comment_continuation: true,
+            nested_block_comments: true,
+            unicode_line_separators: true,
+            lone_slashes: true,
+            forbid_control_chars_in_strings: true,
+            recover_unterminated_strings: true,
+            backtick_strings: true,
+            single_quote_strings: true,
+            string_delimiter: b'\'',
+            skip_bom: true,
+            strip_leading_shebang: true,
+            preserve_comment_prefix: CommentPrefix::new(b"!"),
+            scan_strategy: ScanStrategy::Branch,
+            fill: b'.',
+        };
+        let builder = StripCommentsBuilder::new()
+            .block_comments(false)
+            .slash_line_comments(false)
+            .hash_line_comments(false)
+            .trailing_commas(false)
+            .elide_empty_elements(true)
+            .line_comment_continuation(true)
+            .nested_block_comments(true)
+            .unicode_line_separators(true)
+            .lone_slashes(true)
+            .forbid_control_chars_in_strings(true)
+            .recover_unterminated_strings(true)
+            .backtick_strings(true)
+            .single_quote_strings(true)
+            .string_delimiter(b'\'')
+            .skip_bom(true)
+            .strip_leading_shebang(true)
+            .preserve_comment_prefix(CommentPrefix::new(b"!"))
+            .scan_strategy(ScanStrategy::Branch)
+            .fill(b'.');
+        assert_eq!(format!("{:?}", builder.settings), format!("{settings:?}"));
+
+        let mut out = String::new();
+        StripCommentsBuilder::new()
+            .preserve_comment_prefix(CommentPrefix::new(b"!"))
+            .single_quote_strings(true)
+            .build("['/*', /*! keep */ /* drop */]".as_bytes())
             .read_to_string(&mut out)
             .unwrap();
-        assert_eq!(count, input.len());
-        out
+        assert_eq!(out, "['/*'  /*! keep */           ]");
     }
 
     #[test]
-    fn block_comments() {
-        let json = r#"{/* Comment */"hi": /** abc */ "bye"}"#;
-        let stripped = strip_string(json);
-        assert_eq!(stripped, r#"{             "hi":            "bye"}"#);
+    fn slash_at_read_boundary() {
+        for chunk in 1..4 {
+            assert_eq!(strip_chunked("1 // c\n", chunk).unwrap(), "1     \n");
+            assert_eq!(strip_chunked("1 /* c */", chunk).unwrap(), "1        ");
+            assert_eq!(strip_chunked("1 /**/", chunk).unwrap(), "1     ");
+            let err = strip_chunked("1 /x", chunk).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+            let err = strip_chunked("1 /", chunk).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+
+        let mut state = Top;
+        let mut first = *b"1 /";
+        strip_buf(&mut state, &mut first, CommentSettings::all()).unwrap();
+        assert_eq!((&first, state), (b"1  ", InComment));
+        for (next, expected) in [(b'/', InLineComment), (b'*', InBlockComment)] {
+            let mut state = InComment;
+            let mut second = [next];
+            strip_buf(&mut state, &mut second, CommentSettings::all()).unwrap();
+            assert_eq!((second, state), ([b' '], expected));
+        }
+        strip_buf(&mut InComment, &mut [b'x'], CommentSettings::all()).unwrap_err();
     }
 
     #[test]
-    fn block_comments_with_possible_end() {
-        let json = r#"{/* Comment*PossibleEnd */"hi": /** abc */ "bye"}"#;
-        let stripped = strip_string(json);
+    fn stripped_lines() {
+        let input = "{\"a\": 1} // x\r\n# y\n/* z\n */{\"b\": \"//\"}\n[1,\n]";
+        for chunk in 1..=4 {
+            let lines: Vec<String> = StripComments::new(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            })
+            .stripped_lines()
+            .collect::<Result<_>>()
+            .unwrap();
+            assert_eq!(
+                lines,
+                [
+                    "{\"a\": 1}     ",
+                    "   ",
+                    "        {\"b\": \"//\"}",
+                    "[1 ",
+                    "]"
+                ],
+                "chunks of {chunk}"
+            );
+        }
+
+        let mut lines = StripComments::new("[1]\n/* open".as_bytes()).stripped_lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "[1]");
         assert_eq!(
-            stripped,
-            r#"{                         "hi":            "bye"}"#
+            lines.next().unwrap().unwrap_err().kind(),
+            ErrorKind::InvalidData
         );
     }
 
-    // See https://github.com/tmccombs/json-comments-rs/issues/12
-    // Make sure we can parse a block comment that ends with more than one "*"
     #[test]
-    fn doc_comment() {
-        let json = r##"/** C **/ { "foo": 123 }"##;
-        let stripped = strip_string(json);
-        assert_eq!(stripped, r##"          { "foo": 123 }"##);
+    fn lone_slashes() {
+        let settings = CommentSettings {
+            lone_slashes: true,
+            ..CommentSettings::all()
+        };
+        for (input, expected) in [
+            ("{foo/bar: 1, a/: [/, 2/,]}", "{foo/bar: 1, a/: [/, 2/ ]}"),
+            (
+                "{a/b: 1 /* c */, d: 2 // e\n}",
+                "{a/b: 1        , d: 2     \n}",
+            ),
+            ("[1, /]", "[1, /]"),
+            ("[1, /\"a\", #/\n//\n]", "[1, /\"a\"    \n  \n]"),
+            ("[1,//x\n/**/]", "[1    \n    ]"),
+            ("a/", "a/"),
+        ] {
+            let mut s = String::from(input);
+            strip_comments_in_place_strict(&mut s, settings).unwrap();
+            assert_eq!(s, expected, "{input:?}");
+            for chunk in 1..=4 {
+                let mut reader = StripComments::with_settings(
+                    settings,
+                    Chunked {
+                        bytes: input.as_bytes(),
+                        chunk,
+                    },
+                );
+                let mut out = String::new();
+                reader.read_to_string(&mut out).unwrap();
+                assert_eq!(out, expected, "{input:?} in chunks of {chunk}");
+            }
+        }
+
+        let mut reader = settings.strip_comments(Chunked {
+            bytes: b"[a/ /* b */ //c\n]",
+            chunk: 1,
+        });
+        reader.read_to_string(&mut String::new()).unwrap();
+        let stats = reader.stats();
+        assert_eq!((stats.comments, stats.bytes_blanked), (2, 10));
+
+        // Off by default.
+        let err = strip(&mut String::from("{foo/bar: 1}")).unwrap_err();
+        assert_eq!(
+            strip_error(&err),
+            StripError::new(StripErrorKind::InvalidCommentStart, 5)
+        );
     }
 
     #[test]
-    fn line_comments() {
-        let json = r#"{
-            // line comment
-            "a": 4,
-            # another
-        }"#;
+    fn block_comment_at_read_boundary() {
+        let input = "[1, /* a *\r\n* b **\r\n*/ 2 /***/\r\n/* *\r*/, 3 /*\r\n*/]\r\n";
+        // Newlines inside a block comment are blanked along with the rest of it.
+        let expected = "[1,                    2      \r\n       , 3       ]\r\n";
+        for settings in [
+            CommentSettings::all(),
+            CommentSettings {
+                nested_block_comments: true,
+                ..CommentSettings::all()
+            },
+        ] {
+            for split in 0..=input.len() {
+                let (head, tail) = input.as_bytes().split_at(split);
+                let mut out = String::new();
+                StripComments::with_settings(settings, head.chain(tail))
+                    .read_to_string(&mut out)
+                    .unwrap();
+                assert_eq!(out, expected, "split at {split}");
 
-        let expected = "{
-                           \n            \"a\": 4,
-                     \n        }";
+                let (mut head, mut tail) = (head.to_vec(), tail.to_vec());
+                let mut state = Top;
+                strip_buf(&mut state, &mut head, settings).unwrap();
+                strip_buf(&mut state, &mut tail, settings).unwrap();
+                assert_eq!(
+                    [head, tail].concat(),
+                    expected.as_bytes(),
+                    "split at {split}"
+                );
+            }
+        }
+        for chunk in 1..=4 {
+            assert_eq!(strip_chunked(input, chunk).unwrap(), expected);
+        }
+    }
 
-        assert_eq!(strip_string(json), expected);
+    #[test]
+    fn escapes_at_read_boundary() {
+        let cases = [
+            // An escaped backslash, so the quote ends the string.
+            (r#"["a\\" // c"#, r#"["a\\"     "#),
+            // An escaped quote, so the string runs on over the comment-like text.
+            (r#"["a\" // c"]"#, r#"["a\" // c"]"#),
+            (r#"["a\\\" // c"]"#, r#"["a\\\" // c"]"#),
+            (r#"["a\\\\" /**/]"#, r#"["a\\\\"     ]"#),
+        ];
+        for (input, expected) in cases {
+            for split in 0..=input.len() {
+                let (first, second) = input.as_bytes().split_at(split);
+                let mut out = String::new();
+                StripComments::new(first.chain(second))
+                    .read_to_string(&mut out)
+                    .unwrap();
+                assert_eq!(out, expected, "split at {split}");
+            }
+        }
+
+        let mut state = InString;
+        strip_buf(&mut state, &mut b"a\\".to_owned(), CommentSettings::all()).unwrap();
+        assert_eq!(state, StringEscape);
+        strip_buf(&mut state, &mut b"\\".to_owned(), CommentSettings::all()).unwrap();
+        assert_eq!(state, InString);
+        strip_buf(&mut state, &mut b"\"".to_owned(), CommentSettings::all()).unwrap();
+        assert_eq!(state, Top);
     }
 
     #[test]
-    fn incomplete_string() {
-        let json = r#""foo"#;
-        let mut stripped = String::new();
+    fn extend_collectors() {
+        let input = b"{\"a\": [1, 2,], // c\n \"b\": \"/* s */\"} # d";
+        let mut out = Vec::new();
+        strip_extend(input, &mut out).unwrap();
+        assert_eq!(out, strip_bytes(input).unwrap());
 
-        let err = StripComments::new(json.as_bytes())
-            .read_to_string(&mut stripped)
-            .unwrap_err();
+        /// Counts bytes without storing them.
+        #[derive(Default)]
+        struct Count {
+            bytes: usize,
+            spaces: usize,
+        }
+
+        impl Extend<u8> for Count {
+            fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+                for c in iter {
+                    self.bytes += 1;
+                    self.spaces += usize::from(c == b' ');
+                }
+            }
+        }
+
+        let long = "[1] // comment\n".repeat(1000);
+        let mut count = Count::default();
+        strip_extend(long.as_bytes(), &mut count).unwrap();
+        assert_eq!((count.bytes, count.spaces), (long.len(), 11 * 1000));
+
+        let mut out = Vec::new();
+        let err = strip_extend(b"[1] /* open", &mut out).unwrap_err();
         assert_eq!(err.kind(), ErrorKind::InvalidData);
+        assert_eq!(out, b"[1]        ");
     }
 
     #[test]
-    fn incomplete_comment() {
-        let json = "/* foo ";
-        let mut stripped = String::new();
+    fn diff_hunks() {
+        let input = "@@ -1,4 +1,6 @@\n {\n+  /* a block comment\n+     over added lines */\n-  \"a\": [1,\n+  \"a\": [1, 2, // two\n+  ],\n   \"b\": \"-/* kept */\"\n }";
+        let mut s = String::from(input);
+        strip_diff_hunk(&mut s).unwrap();
+        assert_eq!(
+            s,
+            "@@ -1,4 +1,6 @@\n {\n+                    \n+                        \n-  \"a\": [1,\n+  \"a\": [1, 2        \n+  ],\n   \"b\": \"-/* kept */\"\n }"
+        );
 
-        let err = StripComments::new(json.as_bytes())
-            .read_to_string(&mut stripped)
-            .unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let mut s = String::from("+[1,\n+]");
+        strip_diff_hunk(&mut s).unwrap();
+        assert_eq!(s, "+[1 \n+]");
+
+        // The two sides are stripped separately.
+        let mut s = String::from("-[1,\n+[1, 2,\n ]");
+        strip_diff_hunk(&mut s).unwrap();
+        assert_eq!(s, "-[1 \n+[1, 2 \n ]");
+
+        let mut s = String::from("-  /* old\n+  1\n-  */\n+  // new\n ");
+        strip_diff_hunk(&mut s).unwrap();
+        assert_eq!(s, "-        \n+  1\n-    \n+        \n ");
+
+        // Hunk headers are left alone and don't affect the lines around them.
+        for header in [
+            "@@ -1,2 +1,2 @@ # section",
+            "@@ -1 +1 @@ it's \"open",
+            "@@ a/b @@ /x",
+        ] {
+            let mut s = format!("{header}\n+[1, // c\n+]\n-[2, /* d\n{header}\n- */]");
+            strip_diff_hunk(&mut s).unwrap();
+            assert_eq!(
+                s,
+                format!("{header}\n+[1      \n+]\n-[2      \n{header}\n-   ]")
+            );
+        }
+
+        let mut s = String::from("+[1] // ok\n+/x");
+        strip_diff_hunk(&mut s).unwrap_err();
+        assert_eq!(s, "+[1]      \n+ x");
     }
 
     #[test]
-    fn incomplete_comment2() {
-        let json = "/* foo *";
-        let mut stripped = String::new();
+    fn value_boundaries() {
+        let boundaries_with = |settings, input: &'static str, chunk: usize| {
+            let (send, recv) = std::sync::mpsc::channel();
+            let chunked = Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            };
+            let mut reader = StripComments::with_settings(settings, chunked)
+                .on_value_boundary(move |offset| send.send(offset).unwrap());
+            reader.read_to_string(&mut String::new()).unwrap();
+            recv.try_iter().collect::<Vec<_>>()
+        };
+        let boundaries = |input, chunk| boundaries_with(CommentSettings::all(), input, chunk);
 
-        let err = StripComments::new(json.as_bytes())
-            .read_to_string(&mut stripped)
-            .unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        for chunk in [1, 2, 3, 64] {
+            assert_eq!(boundaries("{}{}{}", chunk), [2, 4, 6]);
+            assert_eq!(
+                boundaries("{\"a\": [\"}\"]} /* {} */\n[[1], {}]\n", chunk),
+                [12, 31]
+            );
+            assert_eq!(
+                boundaries("1 true// x\n\"\\\"]\"null", chunk),
+                [1, 6, 16, 20]
+            );
+            assert!(boundaries("  // only comments\n", chunk).is_empty());
+
+            // Strings follow the settings, and a comment separates values.
+            let settings = CommentSettings {
+                single_quote_strings: true,
+                backtick_strings: true,
+                fill: b'_',
+                ..CommentSettings::all()
+            };
+            assert_eq!(
+                boundaries_with(settings, "'}' `\"` 1/**/2 [\"'\"]", chunk),
+                [3, 7, 9, 14, 20]
+            );
+
+            // A fill byte that could be part of a number is still a removed comment.
+            let dots = CommentSettings {
+                fill: b'.',
+                ..CommentSettings::all()
+            };
+            assert_eq!(boundaries_with(dots, "1.5 /* a */ 2", chunk), [3, 13]);
+
+            // Kept comments are not values.
+            let keep = CommentSettings {
+                preserve_comment_prefix: CommentPrefix::new(b"!"),
+                ..CommentSettings::all()
+            };
+            assert_eq!(
+                boundaries_with(keep, "/*! (c) \"x */ {} {}", chunk),
+                [16, 19]
+            );
+
+            // A `/` kept as an ordinary byte is part of the value.
+            let slashes = CommentSettings {
+                lone_slashes: true,
+                ..CommentSettings::all()
+            };
+            assert_eq!(boundaries_with(slashes, "a/b 1// c", chunk), [3, 5]);
+            // A shebang is a comment, and a byte order mark is whitespace.
+            let start = CommentSettings {
+                skip_bom: true,
+                strip_leading_shebang: true,
+                hash_line_comments: false,
+                ..CommentSettings::all()
+            };
+            assert_eq!(boundaries_with(start, "#!/bin/x {}\n1", chunk), [13]);
+            assert_eq!(boundaries_with(start, "\u{feff}1", chunk), [4]);
+        }
     }
 
     #[test]
-    fn no_hash_comments() {
-        let json = r#"# bad comment
-        {"a": "b"}"#;
-        let mut stripped = String::new();
-        CommentSettings::c_style()
-            .strip_comments(json.as_bytes())
-            .read_to_string(&mut stripped)
+    fn buf_read() {
+        let input =
+            "{\"a\": [1, 2, ], // x, ]\n \"b\": \"/* ,] */\", /* c\n */ \"d\": 1,\n}\n[1] # y";
+        let expected = strip_str(input).unwrap();
+        for chunk in 1..=input.len() {
+            let mut reader = StripComments::new(Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            });
+            let mut lines = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            assert_eq!(lines.concat(), expected, "chunks of {chunk}");
+            assert_eq!(lines.len(), 4);
+        }
+
+        // Reads and buffered reads may be mixed.
+        let mut reader = StripComments::new(Chunked {
+            bytes: input.as_bytes(),
+            chunk: 3,
+        });
+        let mut out = vec![0; 5];
+        reader.read_exact(&mut out).unwrap();
+        let buffered = reader.fill_buf().unwrap().to_vec();
+        reader.consume(1);
+        out.push(buffered[0]);
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, expected.as_bytes());
+        assert_eq!(reader.fill_buf().unwrap(), b"");
+
+        let (send, recv) = std::sync::mpsc::channel();
+        let mut reader = StripComments::new("{} [1,] // x\n2".as_bytes())
+            .on_value_boundary(move |offset| send.send(offset).unwrap());
+        let lines: Vec<_> = reader.by_ref().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["{} [1 ]     ", "2"]);
+        assert_eq!(recv.try_iter().collect::<Vec<_>>(), [2, 7, 14]);
+
+        let mut reader = StripComments::new("[1] /* open".as_bytes());
+        let err = reader.read_line(&mut String::new()).unwrap_err();
+        assert_eq!(
+            strip_error(&err).kind(),
+            StripErrorKind::UnterminatedBlockComment
+        );
+    }
+
+    #[test]
+    fn fill_byte() {
+        let settings = CommentSettings {
+            fill: b'.',
+            ..CommentSettings::all()
+        };
+        let input = "{\"a\": [1, # x\n2, /* y\n*/],} // z";
+        let expected = "{\"a\": [1, ...\n2. .......].} ....";
+        let mut s = String::from(input);
+        strip_comments_in_place(&mut s, settings).unwrap();
+        assert_eq!(s, expected);
+        for chunk in [1, 3, 64] {
+            let mut out = String::new();
+            let chunked = Chunked {
+                bytes: input.as_bytes(),
+                chunk,
+            };
+            settings
+                .strip_comments(chunked)
+                .read_to_string(&mut out)
+                .unwrap();
+            assert_eq!(out, expected, "chunk {chunk}");
+        }
+
+        let mut out = String::new();
+        StripCommentsBuilder::new()
+            .settings(CommentSettings::c_style())
+            .fill(b'_')
+            .build(input.as_bytes())
+            .read_to_string(&mut out)
             .unwrap();
-        assert_eq!(stripped, json);
+        assert_eq!(out, "{\"a\": [1, # x\n2_ _______]_} ____");
+
+        let mut s = String::from(input);
+        let nul = CommentSettings {
+            fill: b'\0',
+            ..settings
+        };
+        strip_comments_in_place(&mut s, nul).unwrap();
+        assert_eq!(s.replace('\0', ""), "{\"a\": [1, \n2 ]} ");
+
+        let mut s = String::from(input);
+        let non_ascii = CommentSettings {
+            fill: 0xff,
+            ..settings
+        };
+        let err = strip_comments_in_place(&mut s, non_ascii).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert_eq!(s, input);
     }
 
     #[test]
-    fn no_slash_line_comments() {
-        let json = r#"// bad comment
-        {"a": "b"}"#;
-        let mut stripped = String::new();
-        let err = CommentSettings::hash_only()
-            .strip_comments(json.as_bytes())
-            .read_to_string(&mut stripped)
-            .unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    fn cow_borrows_clean_input() {
+        let clean = [
+            "",
+            "{\"a\": [1, 2], \"b\": {\"c\": null}}",
+            "[\"a, ]\", \"// no\", \"# no\", \"/* no */\"]",
+            "[1,\n 2 ,3]",
+        ];
+        for input in clean {
+            assert!(matches!(strip_cow(input).unwrap(), Cow::Borrowed(s) if s == input));
+        }
+
+        for input in [
+            "[1,]",
+            "{\"a\": 1 , \n}",
+            "1 // x",
+            "# x",
+            "[1 /**/]",
+            "[\"\\\"\",]",
+        ] {
+            let stripped = strip_cow(input).unwrap();
+            assert!(matches!(stripped, Cow::Owned(_)), "{input}");
+            assert_eq!(stripped, strip_str(input).unwrap());
+        }
+        strip_cow("[1, /x]").unwrap_err();
     }
 
     #[test]
-    fn no_block_comments() {
-        let json = r#"/* bad comment */ {"a": "b"}"#;
-        let mut stripped = String::new();
-        let err = CommentSettings::hash_only()
-            .strip_comments(json.as_bytes())
-            .read_to_string(&mut stripped)
-            .unwrap_err();
-        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    fn check_reports_first_comment_or_trailing_comma() {
+        for input in [
+            "",
+            "{\"a\": [1, 2], \"b\": {\"c\": null}}",
+            "[\"a, ]\", \"// no\", \"# no\", \"/* no */\"]",
+            "[1,\n 2 ,3]",
+        ] {
+            assert_eq!(check(input), Ok(()), "{input}");
+        }
+
+        let comment = StripErrorKind::Comment;
+        let trailing = StripErrorKind::TrailingComma;
+        for (input, kind, offset) in [
+            ("[1, // x\n 2]", comment, 4),
+            ("{\"a\": 1} # x", comment, 9),
+            ("[1 /* x */, 2,]", comment, 3),
+            ("{\"a\": 1 , \n}", trailing, 8),
+            ("[[1,], 2, // x\n]", trailing, 3),
+            ("[1, /x]", StripErrorKind::InvalidCommentStart, 5),
+            ("[1] /", StripErrorKind::InvalidCommentStart, 5),
+            ("[\"a\", \"b", StripErrorKind::UnterminatedString, 6),
+        ] {
+            assert_eq!(check(input), Err(StripError::new(kind, offset)), "{input}");
+        }
     }
 
     #[test]
-    fn keep_all() {
-        let original = String::from(
-            r#"
-     {
-         "name": /* full */ "John Doe",
-         "age": 43, # hash line comment
-         "phones": [
-             "+44 1234567", // work phone
-             "+44 2345678", // home phone
-         ], /** comment **/
-     }"#,
-        );
-        let mut changed = original.clone();
-        let _ = strip_comments_in_place(
-            &mut changed,
-            CommentSettings {
-                block_comments: false,
-                slash_line_comments: false,
-                hash_line_comments: false,
-                trailing_commas: false,
-            },
+    fn reader_to_owned() {
+        let input = "{\"a\": [1, 2,], // x\n}";
+        let expected = strip_str(input).unwrap();
+        let chunked = Chunked {
+            bytes: input.as_bytes(),
+            chunk: 3,
+        };
+        assert_eq!(strip_reader_to_string(chunked).unwrap(), expected);
+        assert_eq!(
+            strip_reader_to_vec(input.as_bytes()).unwrap(),
+            expected.as_bytes()
         );
-        assert_eq!(original, changed);
+
+        for (input, kind, offset) in [
+            ("[1] /* open", StripErrorKind::UnterminatedBlockComment, 4),
+            ("[\"open", StripErrorKind::UnterminatedString, 1),
+        ] {
+            let expected = StripError::new(kind, offset);
+            let err = strip_reader_to_string(input.as_bytes()).unwrap_err();
+            assert_eq!(strip_error(&err), expected);
+            let err = strip_reader_to_vec(input.as_bytes()).unwrap_err();
+            assert_eq!(strip_error(&err), expected);
+        }
+        let err = strip_reader_to_string(&b"[\"\xff\"]"[..]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
     }
 
     #[test]
-    fn strip_in_place() {
-        let mut json = String::from(r#"{/* Comment */"hi": /** abc */ "bye"}"#);
-        strip_comments_in_place(&mut json, CommentSettings::default()).unwrap();
-        assert_eq!(json, r#"{             "hi":            "bye"}"#);
+    fn inner_reader() {
+        let mut reader = StripComments::new("[1] // one\n[2] // two\n".as_bytes());
+        let mut first = [0; 11];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(&first, b"[1]       \n");
+        assert_eq!(reader.get_ref(), b"[2] // two\n");
+
+        let mut byte = [0; 1];
+        reader.get_mut().read_exact(&mut byte).unwrap();
+        assert_eq!(&byte, b"[");
+
+        let mut rest = String::new();
+        reader.into_inner().read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "2] // two\n");
     }
 
     #[test]
-    fn trailing_comma() {
-        let mut json = String::from(
-            r#"{
-            "a1": [1,],
-            "a2": [1,/* x */],
-            "a3": [
-                1, // x
-            ],
-            "o1": {v:1,},
-            "o2": {v:1,/* x */},
-            "o3": {
-                "v":1, // x
-            },
-            # another
-        }"#,
-        );
-        strip_comments_in_place(&mut json, CommentSettings::default()).unwrap();
-
-        let expected = r#"{
-            "a1": [1 ],
-            "a2": [1        ],
-            "a3": [
-                1
-            ],
-            "o1": {v:1 },
-            "o2": {v:1        },
-            "o3": {
-                "v":1
+    fn reset_between_documents() {
+        let input = "{\"a\": \"/*\"} // one\n\u{1e}[1, 2,] # two\n\u{1e}\"three\" /* unterminated";
+        let mut reader = StripComments::new(input.as_bytes());
+        let mut docs = Vec::new();
+        let mut doc = Vec::new();
+        let mut byte = [0; 1];
+        // The last document is cut short, which is still an error.
+        while let Ok(1) = reader.read(&mut byte) {
+            if byte[0] == 0x1e {
+                docs.push(String::from_utf8(std::mem::take(&mut doc)).unwrap());
+                reader.reset();
+            } else {
+                doc.push(byte[0]);
             }
-        }"#;
+        }
+        assert_eq!(docs, ["{\"a\": \"/*\"}       \n", "[1, 2 ]      \n"]);
+        assert_eq!(doc, b"\"three\"                ");
 
-        assert_eq!(
-            json.replace(|s: char| s.is_ascii_whitespace(), ""),
-            expected.replace(|s: char| s.is_ascii_whitespace(), "")
-        );
+        reader.reset();
+        assert_eq!(reader.read(&mut byte).unwrap(), 0);
     }
 }
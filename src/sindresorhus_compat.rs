@@ -0,0 +1,357 @@
+//! A port of the JavaScript [strip-json-comments](https://github.com/sindresorhus/strip-json-comments)
+//! package, for output identical to it.
+//!
+//! This differs from the rest of the crate in several ways, all matching the JavaScript
+//! package:
+//!   - Only `//` and `/* ... */` comments are recognized; `#` is not a comment.
+//!   - Trailing commas are kept unless [`Options::trailing_commas`] is set.
+//!   - Whitespace inside comments, including newlines, is kept when replacing with whitespace.
+//!   - Other characters are replaced with one space per UTF-16 code unit rather than per byte.
+//!   - Malformed input never errors. An unterminated block comment is left as it is.
+//!
+//! ## Example
+//! ```
+//! use json_strip_comments::sindresorhus_compat::{strip, Options};
+//!
+//! let input = "{\"a\": [1,], /* c\n */}";
+//! assert_eq!(strip(input, Options::default()), "{\"a\": [1,],     \n   }");
+//!
+//! let options = Options { whitespace: false, trailing_commas: true };
+//! assert_eq!(strip(input, options), "{\"a\": [1] }");
+//! ```
+
+/// Options matching those of the JavaScript package.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Options {
+    /// Replace comments and trailing commas with whitespace instead of removing them.
+    ///
+    /// Defaults to `true`.
+    pub whitespace: bool,
+    /// Strip trailing commas in addition to comments.
+    ///
+    /// Defaults to `false`.
+    pub trailing_commas: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            whitespace: true,
+            trailing_commas: false,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Comment {
+    Single,
+    Multi,
+}
+
+/// Strips comments exactly like `stripJsonComments(input, options)` in JavaScript.
+pub fn strip(input: &str, options: Options) -> String {
+    let bytes = input.as_bytes();
+    let strip_range = |s: &str| -> String {
+        if options.whitespace {
+            replace_non_whitespace(s)
+        } else {
+            String::new()
+        }
+    };
+
+    let mut inside_string = false;
+    let mut inside_comment = None;
+    let mut offset = 0;
+    let mut buffer = String::new();
+    let mut result = String::new();
+    let mut comma_index = None;
+
+    // Every position compared against is an ASCII byte, so slicing at them is always valid.
+    let mut index = 0;
+    while index < bytes.len() {
+        let current = bytes[index];
+        let next = bytes.get(index + 1).copied();
+
+        if inside_comment.is_none() && current == b'"' && !is_escaped(bytes, index) {
+            inside_string = !inside_string;
+        }
+
+        if inside_string {
+            index += 1;
+            continue;
+        }
+
+        if inside_comment.is_none() && current == b'/' && next == Some(b'/') {
+            buffer.push_str(&input[offset..index]);
+            offset = index;
+            inside_comment = Some(Comment::Single);
+            index += 1;
+        } else if inside_comment == Some(Comment::Single) && current == b'\r' && next == Some(b'\n')
+        {
+            index += 1;
+            inside_comment = None;
+            buffer.push_str(&strip_range(&input[offset..index]));
+            offset = index;
+        } else if inside_comment == Some(Comment::Single) && current == b'\n' {
+            inside_comment = None;
+            buffer.push_str(&strip_range(&input[offset..index]));
+            offset = index;
+        } else if inside_comment.is_none() && current == b'/' && next == Some(b'*') {
+            buffer.push_str(&input[offset..index]);
+            offset = index;
+            inside_comment = Some(Comment::Multi);
+            index += 1;
+        } else if inside_comment == Some(Comment::Multi) && current == b'*' && next == Some(b'/') {
+            index += 1;
+            inside_comment = None;
+            buffer.push_str(&strip_range(&input[offset..=index]));
+            offset = index + 1;
+        } else if options.trailing_commas && inside_comment.is_none() {
+            if comma_index.is_some() {
+                if current == b'}' || current == b']' {
+                    buffer.push_str(&input[offset..index]);
+                    // The buffer starts with the comma.
+                    result.push_str(&strip_range(&buffer[..1]));
+                    result.push_str(&buffer[1..]);
+                    buffer.clear();
+                    offset = index;
+                    comma_index = None;
+                } else if !matches!(current, b' ' | b'\t' | b'\r' | b'\n') {
+                    buffer.push_str(&input[offset..index]);
+                    offset = index;
+                    comma_index = None;
+                }
+            } else if current == b',' {
+                result.push_str(&buffer);
+                result.push_str(&input[offset..index]);
+                buffer.clear();
+                offset = index;
+                comma_index = Some(index);
+            }
+        }
+        index += 1;
+    }
+
+    let remaining = &input[offset..];
+    let remaining = if inside_comment == Some(Comment::Single) {
+        strip_range(remaining)
+    } else {
+        remaining.to_owned()
+    };
+    result + &buffer + &remaining
+}
+
+fn is_escaped(bytes: &[u8], quote: usize) -> bool {
+    let backslashes = bytes[..quote]
+        .iter()
+        .rev()
+        .take_while(|&&c| c == b'\\')
+        .count();
+    backslashes % 2 == 1
+}
+
+/// `s.replace(/\S/g, ' ')`, where JavaScript counts UTF-16 code units.
+fn replace_non_whitespace(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if is_js_whitespace(c) {
+            out.push(c);
+        } else {
+            out.extend(std::iter::repeat_n(' ', c.len_utf16()));
+        }
+    }
+    out
+}
+
+/// Characters matched by `\s` in JavaScript regular expressions.
+fn is_js_whitespace(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\u{b}' | '\u{c}' | '\r' | ' ' | '\u{a0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200a}'
+                | '\u{2028}'
+                | '\u{2029}'
+                | '\u{202f}'
+                | '\u{205f}'
+                | '\u{3000}'
+                | '\u{feff}'
+    )
+}
+
+// Ported from the JavaScript package's test suite.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip_default(input: &str) -> String {
+        strip(input, Options::default())
+    }
+
+    const REMOVE: Options = Options {
+        whitespace: false,
+        trailing_commas: false,
+    };
+
+    #[test]
+    fn replace_comments_with_whitespace() {
+        assert_eq!(
+            strip_default("//comment\n{\"a\":\"b\"}"),
+            "         \n{\"a\":\"b\"}"
+        );
+        assert_eq!(
+            strip_default("/*//comment*/{\"a\":\"b\"}"),
+            "             {\"a\":\"b\"}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\"//comment\n}"),
+            "{\"a\":\"b\"         \n}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\"/*comment*/}"),
+            "{\"a\":\"b\"           }"
+        );
+        assert_eq!(
+            strip_default("{\"a\"/*\n\n\ncomment\r\n*/:\"b\"}"),
+            "{\"a\"  \n\n\n       \r\n  :\"b\"}"
+        );
+        assert_eq!(
+            strip_default("/*!\n * comment\n */\n{\"a\":\"b\"}"),
+            "   \n          \n   \n{\"a\":\"b\"}"
+        );
+        assert_eq!(
+            strip_default("{/*comment*/\"a\":\"b\"}"),
+            "{           \"a\":\"b\"}"
+        );
+    }
+
+    #[test]
+    fn remove_comments() {
+        assert_eq!(strip("//comment\n{\"a\":\"b\"}", REMOVE), "\n{\"a\":\"b\"}");
+        assert_eq!(strip("/*//comment*/{\"a\":\"b\"}", REMOVE), "{\"a\":\"b\"}");
+        assert_eq!(strip("{\"a\":\"b\"//comment\n}", REMOVE), "{\"a\":\"b\"\n}");
+        assert_eq!(strip("{\"a\":\"b\"/*comment*/}", REMOVE), "{\"a\":\"b\"}");
+        assert_eq!(
+            strip("{\"a\"/*\n\n\ncomment\r\n*/:\"b\"}", REMOVE),
+            "{\"a\":\"b\"}"
+        );
+        assert_eq!(
+            strip("/*!\n * comment\n */\n{\"a\":\"b\"}", REMOVE),
+            "\n{\"a\":\"b\"}"
+        );
+        assert_eq!(strip("{/*comment*/\"a\":\"b\"}", REMOVE), "{\"a\":\"b\"}");
+    }
+
+    #[test]
+    fn does_not_strip_comments_inside_strings() {
+        for input in [
+            r#"{"a":"b//c"}"#,
+            r#"{"a":"b/*c*/"}"#,
+            r#"{"/*a":"b"}"#,
+            r#"{"\"/*a":"b"}"#,
+        ] {
+            assert_eq!(strip_default(input), input);
+        }
+    }
+
+    #[test]
+    fn consider_escaped_slashes() {
+        for input in [
+            r#"{"\\":"https://foobar.com"}"#,
+            r#"{"foo\"":"https://foobar.com"}"#,
+        ] {
+            assert_eq!(strip_default(input), input);
+        }
+    }
+
+    #[test]
+    fn line_endings() {
+        assert_eq!(strip_default("{\"a\":\"b\"\n}"), "{\"a\":\"b\"\n}");
+        assert_eq!(strip_default("{\"a\":\"b\"\r\n}"), "{\"a\":\"b\"\r\n}");
+        assert_eq!(strip_default("{\"a\":\"b\"//c\n}"), "{\"a\":\"b\"   \n}");
+        assert_eq!(
+            strip_default("{\"a\":\"b\"//c\r\n}"),
+            "{\"a\":\"b\"   \r\n}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\"/*c*/\n}"),
+            "{\"a\":\"b\"     \n}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\"/*c*/\r\n}"),
+            "{\"a\":\"b\"     \r\n}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\",/*c\nc2*/\"x\":\"y\"\n}"),
+            "{\"a\":\"b\",   \n    \"x\":\"y\"\n}"
+        );
+        assert_eq!(
+            strip_default("{\"a\":\"b\",/*c\r\nc2*/\"x\":\"y\"\r\n}"),
+            "{\"a\":\"b\",   \r\n    \"x\":\"y\"\r\n}"
+        );
+    }
+
+    #[test]
+    fn works_at_eof() {
+        assert_eq!(
+            strip_default("{\r\n\t\"a\":\"b\"\r\n} //EOF"),
+            "{\r\n\t\"a\":\"b\"\r\n}      "
+        );
+        assert_eq!(
+            strip("{\r\n\t\"a\":\"b\"\r\n} //EOF", REMOVE),
+            "{\r\n\t\"a\":\"b\"\r\n} "
+        );
+    }
+
+    #[test]
+    fn weird_escaping() {
+        let input =
+            r#"{"x":"x \"sed -e \\\"s/^.\\\\{46\\\\}T//\\\" -e \\\"s/#033/\\\\x1b/g\\\"\""}"#;
+        assert_eq!(strip_default(input), input);
+    }
+
+    #[test]
+    fn strips_trailing_commas() {
+        let commas = Options {
+            whitespace: true,
+            trailing_commas: true,
+        };
+        let remove_commas = Options {
+            whitespace: false,
+            trailing_commas: true,
+        };
+        assert_eq!(strip("{\"x\":true,}", commas), "{\"x\":true }");
+        assert_eq!(strip("{\"x\":true,}", remove_commas), "{\"x\":true}");
+        assert_eq!(strip("{\"x\":true,\n  }", commas), "{\"x\":true \n  }");
+        assert_eq!(strip("[true, false,]", remove_commas), "[true, false]");
+        assert_eq!(strip("[true, false,]", commas), "[true, false ]");
+        assert_eq!(
+            strip(
+                "{\n  \"array\": [\n    true,\n    false,\n  ],\n}",
+                remove_commas
+            ),
+            "{\n  \"array\": [\n    true,\n    false\n  ]\n}"
+        );
+        assert_eq!(
+            strip(
+                "{\n  \"array\": [\n    true,\n    false /* comment */ ,\n /*comment*/ ],\n}",
+                remove_commas
+            ),
+            "{\n  \"array\": [\n    true,\n    false  \n  ]\n}"
+        );
+        // Kept by default.
+        assert_eq!(strip_default("[true, false,]"), "[true, false,]");
+    }
+
+    #[test]
+    fn malformed_block_comments() {
+        assert_eq!(strip_default("[] */"), "[] */");
+        assert_eq!(strip_default("[] /*"), "[] /*");
+    }
+
+    #[test]
+    fn non_ascii_in_comments() {
+        // One space per UTF-16 code unit, as in JavaScript.
+        assert_eq!(strip_default("/*é😀*/1"), "       1");
+        assert_eq!(strip_default("//\u{a0}x\n1"), "  \u{a0} \n1");
+    }
+}
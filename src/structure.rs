@@ -0,0 +1,773 @@
+//! Structural checks on JSON that has already been stripped of comments.
+//!
+//! Once comments are blanked, the only things left to track are strings and brackets, so these
+//! scans are much simpler than the stripping state machine.
+
+use std::{
+    collections::HashSet,
+    io::{Error, ErrorKind, Result},
+    ops::Range,
+};
+
+use crate::{
+    strip_str_observed, CommentSettings, DuplicateKeyError, Observer, StripError, StripErrorKind,
+    TopLevelCommas,
+};
+
+/// Strips comments and trailing commas, then checks that the result is exactly one complete
+/// JSON value surrounded by optional whitespace.
+///
+/// This catches common config mistakes such as `{"a": 1} garbage` or a missing closing bracket
+/// before the text reaches a JSON parser. The value itself is not validated beyond its
+/// brackets and strings being balanced.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::strip_and_require_single_value;
+///
+/// let mut ok = String::from(r#"{"a": 1} // done"#);
+/// assert!(strip_and_require_single_value(&mut ok).is_ok());
+///
+/// let mut garbage = String::from(r#"{"a": 1} garbage"#);
+/// assert!(strip_and_require_single_value(&mut garbage).is_err());
+/// ```
+pub fn strip_and_require_single_value(s: &mut str) -> Result<()> {
+    strip_single_value_in_place(s, CommentSettings::all())
+}
+
+/// Like [`strip_and_require_single_value`], but using the given settings.
+///
+/// Strings are told apart as the settings say, so with
+/// [`single_quote_strings`](CommentSettings::single_quote_strings) a bracket inside `'...'` is
+/// string contents.
+pub fn strip_single_value_in_place(s: &mut str, settings: CommentSettings) -> Result<()> {
+    strip_single_value_in_place_with_options(s, settings, SingleValueOptions::default())
+}
+
+/// Extra checks for [`strip_single_value_in_place_with_options`].
+///
+/// The default checks nothing more than [`strip_single_value_in_place`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct SingleValueOptions {
+    /// True if comments after the top-level value are an error, so that nothing but whitespace
+    /// may follow it.
+    pub forbid_trailing_content: bool,
+    /// True if a comment directly between two parts of a keyword or number, as in `nul/**/l`,
+    /// is an error. Blanking such a comment would leave broken JSON like `nul    l`.
+    pub forbid_split_tokens: bool,
+}
+
+/// Like [`strip_single_value_in_place`], with [`SingleValueOptions`].
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{
+///     strip_single_value_in_place_with_options, CommentSettings, SingleValueOptions,
+/// };
+///
+/// let options = SingleValueOptions {
+///     forbid_trailing_content: true,
+///     ..SingleValueOptions::default()
+/// };
+/// let settings = CommentSettings::default();
+/// let strip = |s: &str| {
+///     strip_single_value_in_place_with_options(&mut s.to_owned(), settings, options)
+/// };
+///
+/// assert!(strip("// leading comments are fine\n{\"a\": 1}\n").is_ok());
+/// assert!(strip("{\"a\": 1} // but not trailing ones").is_err());
+/// ```
+pub fn strip_single_value_in_place_with_options(
+    s: &mut str,
+    settings: CommentSettings,
+    options: SingleValueOptions,
+) -> Result<()> {
+    /// The start and end of each comment, in order.
+    struct Comments(Vec<(usize, usize)>);
+
+    impl Observer for Comments {
+        fn comment_start(&mut self, pos: usize) {
+            self.0.push((pos, pos));
+        }
+
+        fn comment_end(&mut self, pos: usize) {
+            if let Some(last) = self.0.last_mut() {
+                last.1 = pos;
+            }
+        }
+    }
+
+    let mut comments = Comments(Vec::new());
+    strip_str_observed(s, settings, &mut comments)?;
+
+    if options.forbid_split_tokens {
+        check_split_tokens(s.as_bytes(), &comments.0)?;
+    }
+
+    let mut scanner = Scanner::new(s.as_bytes(), settings);
+    scanner.skip_whitespace();
+    scanner.skip_value()?;
+    let value_end = scanner.pos;
+    scanner.skip_whitespace();
+    if scanner.pos < scanner.buf.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "trailing content after the top-level value",
+        ));
+    }
+    if options.forbid_trailing_content
+        && comments
+            .0
+            .last()
+            .is_some_and(|&(start, _)| start >= value_end)
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "comment after the top-level value",
+        ));
+    }
+    Ok(())
+}
+
+/// Strips a copy of `s` and returns how deeply its objects and arrays nest.
+///
+/// A scalar at the top level has depth 0, `[]` has depth 1, and `{"a": [1]}` has depth 2.
+/// Brackets inside strings and comments don't count. Useful for warning about, or rejecting,
+/// overly nested configs before they reach a recursive parser.
+///
+/// Errors if a bracket is closed by the wrong kind of bracket, closed without being opened, or
+/// never closed.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::max_depth_reached;
+///
+/// assert_eq!(max_depth_reached(r#"{"a": [1, {"b": "]]]"}] /* [[ */}"#).unwrap(), 3);
+/// assert!(max_depth_reached("[1, 2").is_err());
+/// ```
+pub fn max_depth_reached(s: &str) -> Result<usize> {
+    let mut s = s.to_owned();
+    crate::strip_comments_in_place(&mut s, CommentSettings::all())?;
+    Scanner::new(s.as_bytes(), CommentSettings::all()).max_depth()
+}
+
+/// Returns the byte range of `s` from its first to its last byte that is neither whitespace nor
+/// part of a comment, or `None` if there is no such byte.
+///
+/// Trimming `s` to this range drops leading and trailing comments and whitespace while keeping
+/// everything in between, comments included.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::significant_range;
+///
+/// let input = "// header\n{\"a\": /* one */ 1}\n# footer";
+/// let range = significant_range(input).unwrap().unwrap();
+/// assert_eq!(&input[range], "{\"a\": /* one */ 1}");
+///
+/// assert_eq!(significant_range(" /* only */ // comments").unwrap(), None);
+/// ```
+pub fn significant_range(s: &str) -> Result<Option<Range<usize>>> {
+    let mut stripped = s.to_owned();
+    crate::strip(&mut stripped)?;
+    let bytes = stripped.as_bytes();
+    let Some(start) = bytes.iter().position(|c| !c.is_ascii_whitespace()) else {
+        return Ok(None);
+    };
+    let end = bytes
+        .iter()
+        .rposition(|c| !c.is_ascii_whitespace())
+        .unwrap_or(start);
+    Ok(Some(start..end + 1))
+}
+
+/// Strips a copy of `s` and errors if any object in it has the same key twice.
+///
+/// JSON parsers such as `serde_json` silently keep the last of duplicated keys, so an
+/// accidentally repeated key in a config is easy to miss. The error is
+/// [`ErrorKind::InvalidData`] with a [`DuplicateKeyError`] payload giving the key and the offset
+/// of its second occurrence. Keys are compared as written, so `"a"` and `"\u0061"` count as
+/// different keys. Nothing else about the structure is checked.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::{strip_and_reject_duplicate_keys, DuplicateKeyError};
+///
+/// assert!(strip_and_reject_duplicate_keys(r#"{"a": {"a": 1}, "b": [{"a": 2}]}"#).is_ok());
+///
+/// let err = strip_and_reject_duplicate_keys(r#"{"a": 1, /* "a" */ "a": 2}"#).unwrap_err();
+/// let err = err.get_ref().and_then(|e| e.downcast_ref::<DuplicateKeyError>()).unwrap();
+/// assert_eq!((err.key(), err.offset()), ("a", 19));
+/// ```
+pub fn strip_and_reject_duplicate_keys(s: &str) -> Result<()> {
+    let mut stripped = s.to_owned();
+    crate::strip(&mut stripped)?;
+    match Scanner::new(stripped.as_bytes(), CommentSettings::all()).next_duplicate_key() {
+        Some(key) => {
+            let name = stripped[key.start + 1..key.end - 1].to_owned();
+            Err(DuplicateKeyError::new(name, key.start).into())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Errors if a comment sits directly between two bytes of a keyword or number.
+///
+/// Line comments end at a newline, so only block comments can split a token.
+fn check_split_tokens(buf: &[u8], comments: &[(usize, usize)]) -> Result<()> {
+    let is_token = |c: &u8| c.is_ascii_alphanumeric() || matches!(c, b'.' | b'-' | b'+');
+    for &(start, end) in comments {
+        let before = start.checked_sub(1).map(|i| &buf[i]);
+        if !(before.is_some_and(is_token) && buf.get(end).is_some_and(is_token)) {
+            continue;
+        }
+        let token_start = buf[..start]
+            .iter()
+            .rposition(|c| !is_token(c))
+            .map_or(0, |i| i + 1);
+        let kind = if buf[token_start].is_ascii_alphabetic() {
+            "keyword"
+        } else {
+            "number"
+        };
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("comment splits {kind} at offset {start}"),
+        ));
+    }
+    Ok(())
+}
+
+/// Applies `policy` to every comma in `s`, stripped with `settings`, that is outside any
+/// object, array, or string, replacing stripped ones with [`CommentSettings::fill`], which must
+/// be ASCII.
+pub(crate) fn handle_top_level_commas(
+    s: &mut str,
+    policy: TopLevelCommas,
+    settings: CommentSettings,
+) -> core::result::Result<(), StripError> {
+    if policy == TopLevelCommas::Allow {
+        return Ok(());
+    }
+    let mut commas = Vec::new();
+    let mut scanner = Scanner::new(s.as_bytes(), settings);
+    while let Some(pos) = scanner.next_top_level_comma() {
+        if policy == TopLevelCommas::Error {
+            return Err(StripError::new(StripErrorKind::TopLevelComma, pos));
+        }
+        commas.push(pos);
+    }
+    // Safety: only ASCII commas are replaced, with an ASCII byte, so the text stays UTF-8.
+    let buf = unsafe { s.as_bytes_mut() };
+    for &pos in &commas {
+        buf[pos] = settings.fill;
+    }
+    Ok(())
+}
+
+/// A cursor over stripped JSON.
+pub(crate) struct Scanner<'a> {
+    buf: &'a [u8],
+    pub(crate) pos: usize,
+    /// The settings the text was stripped with, which say what starts a string.
+    settings: CommentSettings,
+}
+
+impl<'a> Scanner<'a> {
+    pub(crate) fn new(buf: &'a [u8], settings: CommentSettings) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            settings,
+        }
+    }
+
+    /// Whether `c` starts a string, as it does at the top level of the stripping state machine.
+    fn opens_string(&self, c: u8) -> bool {
+        c == self.settings.string_delimiter
+            || (c == b'\'' && self.settings.single_quote_strings)
+            || (c == b'`' && self.settings.backtick_strings)
+    }
+
+    pub(crate) fn skip_whitespace(&mut self) {
+        while self.buf.get(self.pos).is_some_and(u8::is_ascii_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips over one complete value starting at the cursor.
+    ///
+    /// Errors if there is no value at the cursor, or if the value never completes.
+    pub(crate) fn skip_value(&mut self) -> Result<()> {
+        let Some(&c) = self.buf.get(self.pos) else {
+            return Err(incomplete());
+        };
+        match c {
+            _ if self.opens_string(c) => self.skip_string(),
+            b'{' | b'[' => self.skip_container(),
+            b'}' | b']' | b',' | b':' => Err(Error::new(
+                ErrorKind::InvalidData,
+                "expected a value but found a delimiter",
+            )),
+            _ => {
+                self.skip_scalar();
+                Ok(())
+            }
+        }
+    }
+
+    /// Advances past the next comma outside any container or string, returning its position.
+    pub(crate) fn next_top_level_comma(&mut self) -> Option<usize> {
+        let mut depth = 0usize;
+        while let Some(&c) = self.buf.get(self.pos) {
+            match c {
+                _ if self.opens_string(c) => {
+                    // An unterminated string runs to the end, which ends the loop too.
+                    let _ = self.skip_string();
+                    continue;
+                }
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                b',' if depth == 0 => {
+                    self.pos += 1;
+                    return Some(self.pos - 1);
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    /// Advances to the end, returning the deepest bracket nesting seen on the way.
+    pub(crate) fn max_depth(&mut self) -> Result<usize> {
+        let mut open = Vec::new();
+        let mut max = 0;
+        while let Some(&c) = self.buf.get(self.pos) {
+            match c {
+                b'{' | b'[' => {
+                    open.push((c, self.pos));
+                    max = max.max(open.len());
+                }
+                b'}' | b']' => {
+                    let opener = if c == b'}' { b'{' } else { b'[' };
+                    if open.pop().map(|(c, _)| c) != Some(opener) {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("mismatched bracket at offset {}", self.pos),
+                        ));
+                    }
+                }
+                _ if self.opens_string(c) => {
+                    self.skip_string()?;
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        match open.last() {
+            Some((_, pos)) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unclosed bracket at offset {pos}"),
+            )),
+            None => Ok(max),
+        }
+    }
+
+    /// Advances past the next key that repeats an earlier key of the same object, returning its
+    /// range including the quotes.
+    pub(crate) fn next_duplicate_key(&mut self) -> Option<Range<usize>> {
+        // The keys seen so far in each open container, or `None` for arrays.
+        let mut open: Vec<Option<HashSet<&[u8]>>> = Vec::new();
+        let mut expect_key = false;
+        while let Some(&c) = self.buf.get(self.pos) {
+            match c {
+                b'{' => {
+                    open.push(Some(HashSet::new()));
+                    expect_key = true;
+                }
+                b'[' => {
+                    open.push(None);
+                    expect_key = false;
+                }
+                b'}' | b']' => {
+                    open.pop();
+                    expect_key = false;
+                }
+                b',' => expect_key = matches!(open.last(), Some(Some(_))),
+                _ if self.opens_string(c) => {
+                    let start = self.pos;
+                    self.skip_string().ok()?;
+                    if expect_key {
+                        expect_key = false;
+                        let keys = open.last_mut()?.as_mut()?;
+                        if !keys.insert(&self.buf[start + 1..self.pos - 1]) {
+                            return Some(start..self.pos);
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        None
+    }
+
+    fn skip_container(&mut self) -> Result<()> {
+        let mut closers = Vec::new();
+        while let Some(&c) = self.buf.get(self.pos) {
+            match c {
+                _ if self.opens_string(c) => {
+                    self.skip_string()?;
+                    continue;
+                }
+                b'{' => closers.push(b'}'),
+                b'[' => closers.push(b']'),
+                b'}' | b']' => {
+                    if closers.pop() != Some(c) {
+                        return Err(Error::new(ErrorKind::InvalidData, "mismatched bracket"));
+                    }
+                    if closers.is_empty() {
+                        self.pos += 1;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Err(incomplete())
+    }
+
+    /// Skips over the string opened at the cursor, up to and including the same quote closing it.
+    fn skip_string(&mut self) -> Result<()> {
+        let quote = self.buf[self.pos];
+        let mut escaped = false;
+        self.pos += 1;
+        while let Some(&c) = self.buf.get(self.pos) {
+            self.pos += 1;
+            match c {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                _ if c == quote => return Ok(()),
+                _ => {}
+            }
+        }
+        Err(incomplete())
+    }
+
+    fn skip_scalar(&mut self) {
+        while self.buf.get(self.pos).is_some_and(|&c| {
+            !c.is_ascii_whitespace()
+                && !matches!(c, b'{' | b'}' | b'[' | b']' | b',' | b':')
+                && !self.opens_string(c)
+        }) {
+            self.pos += 1;
+        }
+    }
+}
+
+fn incomplete() -> Error {
+    Error::new(ErrorKind::InvalidData, "the top-level value is incomplete")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(input: &str) -> Result<()> {
+        strip_and_require_single_value(&mut String::from(input))
+    }
+
+    #[test]
+    fn single_value() {
+        check(r#"{"a": [1, 2, {"b": "}"}]} // trailing comment"#).unwrap();
+        check("  /* leading */ [1, 2,]  \n").unwrap();
+        check(r#""string""#).unwrap();
+        check("42 # answer").unwrap();
+        check("null").unwrap();
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        let err = check(r#"{"a": 1} garbage"#).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        check("1 2").unwrap_err();
+    }
+
+    #[test]
+    fn extra_top_level_value() {
+        check(r#"{"a": 1} {"b": 2}"#).unwrap_err();
+        check(
+            r#"[1] // c
+            [2]"#,
+        )
+        .unwrap_err();
+        check(r#""a""b""#).unwrap_err();
+    }
+
+    #[test]
+    fn incomplete_value() {
+        check(r#"{"a": 1"#).unwrap_err();
+        check(r#"{"a": [1}"#).unwrap_err();
+        check(r#""abc"#).unwrap_err();
+        check("// only a comment").unwrap_err();
+        check("").unwrap_err();
+    }
+
+    #[test]
+    fn string_settings() {
+        let check_with =
+            |input: &str, settings| strip_single_value_in_place(&mut String::from(input), settings);
+        let single_quotes = CommentSettings {
+            single_quote_strings: true,
+            ..CommentSettings::default()
+        };
+        check_with("{'a': '}'} // c", single_quotes).unwrap();
+        check_with("['a', \"'\", '\\'']", single_quotes).unwrap();
+        check_with("'a' 'b'", single_quotes).unwrap_err();
+        check("{'a': '}'}").unwrap_err();
+
+        let quote_delimited = CommentSettings {
+            string_delimiter: b'\'',
+            ..CommentSettings::default()
+        };
+        check_with("{'a': '\"'}", quote_delimited).unwrap();
+        check_with("'a'\"", quote_delimited).unwrap_err();
+
+        let backticks = CommentSettings {
+            backtick_strings: true,
+            ..CommentSettings::default()
+        };
+        check_with("[`]`, \"`\"]", backticks).unwrap();
+    }
+
+    #[test]
+    fn forbid_trailing_content() {
+        let strict = SingleValueOptions {
+            forbid_trailing_content: true,
+            ..SingleValueOptions::default()
+        };
+        let check_strict = |input: &str| {
+            strip_single_value_in_place_with_options(
+                &mut String::from(input),
+                CommentSettings::default(),
+                strict,
+            )
+        };
+
+        check(r#"{"a": 1} // comment"#).unwrap();
+        check_strict(r#"{"a": 1} // comment"#).unwrap_err();
+        check_strict(r#"{"a": 1} /* comment */ "#).unwrap_err();
+        check_strict("42# comment").unwrap_err();
+
+        check_strict("/* leading */ {\"a\": /* inner */ 1}  \n\t").unwrap();
+        check_strict("{\"a\": 1}  \n").unwrap();
+        check("{\"a\": 1}  \n").unwrap();
+    }
+
+    #[test]
+    fn forbid_split_tokens() {
+        let strict = SingleValueOptions {
+            forbid_split_tokens: true,
+            ..SingleValueOptions::default()
+        };
+        let check_strict = |input: &str| {
+            strip_single_value_in_place_with_options(
+                &mut String::from(input),
+                CommentSettings::default(),
+                strict,
+            )
+            .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(
+            check_strict("[tr/* */ue]").unwrap_err(),
+            "comment splits keyword at offset 3"
+        );
+        assert_eq!(
+            check_strict("{\"a\": f/**/alse}").unwrap_err(),
+            "comment splits keyword at offset 7"
+        );
+        assert_eq!(
+            check_strict("nul/* */l").unwrap_err(),
+            "comment splits keyword at offset 3"
+        );
+        assert_eq!(
+            check_strict("[1.5/**/e3]").unwrap_err(),
+            "comment splits number at offset 4"
+        );
+        assert_eq!(
+            check_strict("-/**/1").unwrap_err(),
+            "comment splits number at offset 1"
+        );
+
+        // Without the setting, this is only caught as trailing content.
+        check("nul/* */l").unwrap_err();
+        check_strict("[true/**/, /**/null, 1 /**/]").unwrap();
+        check_strict("[\"a/**/b\", 1// c\n]").unwrap();
+    }
+
+    #[test]
+    fn max_depth() {
+        let depth = |input: &str| max_depth_reached(input).map_err(|e| e.to_string());
+
+        assert_eq!(depth("42"), Ok(0));
+        assert_eq!(depth("// nothing"), Ok(0));
+        assert_eq!(depth("[1, 2, 3]"), Ok(1));
+        assert_eq!(depth(r#"{"a": [], "b": {}}"#), Ok(2));
+        assert_eq!(depth(r#"[{"a": [[1]]}, [2]]"#), Ok(4));
+        assert_eq!(
+            depth(&format!("{}{}", "[".repeat(500), "]".repeat(500))),
+            Ok(500)
+        );
+
+        // Brackets in strings and comments don't count.
+        assert_eq!(depth(r#"["[[[", "\"{{"] // [[["#), Ok(1));
+        assert_eq!(depth("{/* [[ */\"a\": 1,}"), Ok(1));
+
+        assert_eq!(depth("[1}"), Err("mismatched bracket at offset 2".into()));
+        assert_eq!(depth("[]]"), Err("mismatched bracket at offset 2".into()));
+        assert_eq!(
+            depth("{\"a\": [1"),
+            Err("unclosed bracket at offset 6".into())
+        );
+        depth(r#"["a]"#).unwrap_err();
+    }
+
+    #[test]
+    fn significant_ranges() {
+        let range = |input: &str| significant_range(input).unwrap();
+
+        assert_eq!(range("[1]"), Some(0..3));
+        assert_eq!(range("/* a */ [1, 2] // b"), Some(8..14));
+        assert_eq!(range("\n# a\n  \"x\"  \r\n"), Some(7..10));
+        assert_eq!(range("1"), Some(0..1));
+        assert_eq!(range("[1, 2,] /*,*/"), Some(0..7));
+
+        assert_eq!(range(""), None);
+        assert_eq!(range(" \n\t"), None);
+        assert_eq!(range("// only\n/* comments */ # here"), None);
+        significant_range("[1] /x").unwrap_err();
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let check = |input: &str| {
+            strip_and_reject_duplicate_keys(input).map_err(|e| {
+                let err = e.get_ref().unwrap().downcast_ref::<DuplicateKeyError>();
+                err.unwrap().to_string()
+            })
+        };
+
+        check(r#"{"a": 1, "b": 2}"#).unwrap();
+        check(r#"{"a": {"a": {"a": 1}}, "b": [{"a": 1}, {"a": 2}]}"#).unwrap();
+        check(r#"{"a": "a", "b": ["a", "a"], "c": {"a": "b"}}"#).unwrap();
+        check(r#"[{"a": 1}, {"a": 2}] // {"a": 3, "a": 4}"#).unwrap();
+        check(r#"{"a": 1, "A": 2, "\u0061": 3}"#).unwrap();
+
+        assert_eq!(
+            check(r#"{"a": 1, "b": 2, "a": 3}"#),
+            Err("duplicate key \"a\" at offset 17".into())
+        );
+        assert_eq!(
+            check(r#"{"x": {"a": [1, {}], /* c */ "a": 2,}}"#),
+            Err("duplicate key \"a\" at offset 29".into())
+        );
+        assert_eq!(
+            check(r#"[{"k\"": 1}, {"k\"": 1, "k\"": 2}]"#),
+            Err("duplicate key \"k\\\"\" at offset 24".into())
+        );
+
+        let err = strip_and_reject_duplicate_keys(r#"{"a": 1} /x"#).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<crate::StripError>());
+    }
+
+    #[test]
+    fn top_level_commas() {
+        let with = |s: &mut String, top_level_commas| {
+            crate::strip_comments_in_place_with_top_level_commas(
+                s,
+                CommentSettings::default(),
+                top_level_commas,
+            )
+        };
+        let input = r#"{"a": [1, 2]} , /* c */ {"b": ","},"#;
+
+        let mut s = String::from(input);
+        with(&mut s, TopLevelCommas::Allow).unwrap();
+        assert_eq!(s, r#"{"a": [1, 2]} ,         {"b": ","},"#);
+
+        let mut s = String::from(input);
+        with(&mut s, TopLevelCommas::Strip).unwrap();
+        assert_eq!(s, r#"{"a": [1, 2]}           {"b": ","} "#);
+
+        let mut s = String::from(input);
+        let err = with(&mut s, TopLevelCommas::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "comma between top-level values at offset 14"
+        );
+        let err = err.get_ref().and_then(|e| e.downcast_ref::<StripError>());
+        assert_eq!(
+            err,
+            Some(&StripError::new(StripErrorKind::TopLevelComma, 14))
+        );
+
+        let mut s = String::from("{} , {}");
+        with(&mut s, TopLevelCommas::Strip).unwrap();
+        assert_eq!(s, "{}   {}");
+        let mut s = String::from("[1, {\"a\": 2,},]");
+        with(&mut s, TopLevelCommas::Error).unwrap();
+
+        // Removed commas are filled like everything else.
+        let mut s = String::from("1, /* two */ 2");
+        let settings = CommentSettings {
+            fill: b'_',
+            ..CommentSettings::default()
+        };
+        crate::strip_comments_in_place_with_top_level_commas(
+            &mut s,
+            settings,
+            TopLevelCommas::Strip,
+        )
+        .unwrap();
+        assert_eq!(s, "1_ _________ 2");
+
+        // Commas in strings are found with the same rules as stripping.
+        let settings = CommentSettings {
+            single_quote_strings: true,
+            backtick_strings: true,
+            ..CommentSettings::default()
+        };
+        let mut s = String::from("'a, b', `c, \"`, \"d, '\"");
+        crate::strip_comments_in_place_with_top_level_commas(
+            &mut s,
+            settings,
+            TopLevelCommas::Strip,
+        )
+        .unwrap();
+        assert_eq!(s, "'a, b'  `c, \"`  \"d, '\"");
+
+        let mut s = String::from("'{', [1, '] // not a comment'] // c");
+        let settings = CommentSettings {
+            string_delimiter: b'\'',
+            ..CommentSettings::default()
+        };
+        crate::strip_comments_in_place_with_top_level_commas(
+            &mut s,
+            settings,
+            TopLevelCommas::Strip,
+        )
+        .unwrap();
+        assert_eq!(s, "'{'  [1, '] // not a comment']     ");
+
+        // An error leaves the text as it was.
+        let input = "[1] /* a */, [2] // b";
+        let mut s = String::from(input);
+        with(&mut s, TopLevelCommas::Error).unwrap_err();
+        assert_eq!(s, input);
+    }
+}
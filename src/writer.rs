@@ -0,0 +1,346 @@
+//! Stripping on the way out, for output written through [`Write`].
+
+use std::io::{Result, Write};
+
+use crate::{
+    ends_lone_slash, error, lookahead, needs_lookahead, partial_start, pending_opening,
+    restore_opening, start_input, strip_chunk, CommentSettings, Lookahead, Observer, Progress,
+    State,
+    State::{InComment, Top},
+    StripError,
+};
+
+/// A [`Write`] that strips comments and trailing commas from what is written to it before
+/// passing it on to another [`Write`], the counterpart of [`StripComments`](crate::StripComments).
+///
+/// State is carried from one `write` to the next, so comments, strings, and trailing commas may
+/// be split across writes. Since a writer has no end of its own, call
+/// [`finish`](Self::finish) once everything is written: it reports input that ended inside a
+/// string or block comment, and writes out a comma held back to see what follows it.
+///
+/// ## Example
+/// ```
+/// use json_strip_comments::StripCommentsWriter;
+/// use std::io::Write;
+///
+/// let mut writer = StripCommentsWriter::new(Vec::new());
+/// writer.write_all(b"{\"a\": [1, 2,] /* two ").unwrap();
+/// writer.write_all(b"*/} // done").unwrap();
+///
+/// assert_eq!(writer.finish().unwrap(), b"{\"a\": [1, 2 ]          }        ");
+/// ```
+pub struct StripCommentsWriter<W: Write> {
+    inner: W,
+    state: State,
+    settings: CommentSettings,
+    progress: Progress,
+    /// Stripped output from a comma that may still turn out to be trailing onwards, from a comma
+    /// or bracket that may be followed by commas to elide, from a `/` that may start a comment,
+    /// or from the opening of a comment that may be kept.
+    held: Vec<u8>,
+    /// The start of the input while it is too short to tell whether a whole byte order mark or
+    /// shebang follows.
+    partial_start: Vec<u8>,
+}
+
+impl<W: Write> StripCommentsWriter<W> {
+    /// Creates a writer that strips with the default settings.
+    pub const fn new(inner: W) -> Self {
+        Self::with_settings(CommentSettings::all(), inner)
+    }
+
+    /// Creates a writer that strips with `settings`.
+    pub const fn with_settings(settings: CommentSettings, inner: W) -> Self {
+        Self {
+            inner,
+            state: Top,
+            settings,
+            progress: Progress::new(),
+            held: Vec::new(),
+            partial_start: Vec::new(),
+        }
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Gets a mutable reference to the underlying writer.
+    ///
+    /// Writing to it directly skips stripping, and may put its output ahead of a held comma.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Checks that the input ended outside any string or block comment, writes out anything
+    /// held back, and returns the underlying writer.
+    ///
+    /// The error for unterminated input carries a [`StripError`], as with
+    /// [`StripComments`](crate::StripComments).
+    pub fn finish(mut self) -> Result<W> {
+        if !self.partial_start.is_empty() {
+            let partial = std::mem::take(&mut self.partial_start);
+            self.strip_and_write(partial)?;
+        }
+        // Nothing follows a held comma, so it is not trailing.
+        self.inner.write_all(&self.held)?;
+        if !self.state.is_complete(self.settings) {
+            let err = StripError::at_eof(self.state, self.progress.start, self.progress.consumed);
+            return Err(err.into());
+        }
+        self.progress.finish(self.state);
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn strip_and_write(&mut self, mut input: Vec<u8>) -> Result<()> {
+        if self.progress.consumed == 0 {
+            self.state = start_input(&mut input, self.settings, &mut self.progress);
+        }
+        let result = self.strip(&mut input);
+        let base = self.progress.consumed;
+        self.progress.consumed += input.len();
+        let ready = result.map_err(|err| error::offset_by(err, base))?;
+        if self.held.is_empty() {
+            self.inner.write_all(&input[..ready])?;
+            self.held.extend_from_slice(&input[ready..]);
+        } else {
+            self.held.extend_from_slice(&input);
+        }
+        Ok(())
+    }
+
+    /// Strips `input`, returning how much of it can be written out. The rest starts with a comma
+    /// that may be trailing, a comma or bracket that may be followed by commas to elide, a `/`
+    /// that may start a comment, or the opening of a comment that may be kept.
+    fn strip(&mut self, input: &mut [u8]) -> Result<usize> {
+        if self.state == InComment && self.settings.lone_slashes {
+            // The last input ended with a `/` that was kept and held back in case it did not
+            // start a comment.
+            let slash = self.held.len() - 1;
+            if !ends_lone_slash(self.state, input[0], self.settings) {
+                self.held[slash] = self.settings.fill;
+                self.progress.comment_start_before_input();
+            }
+            if slash == 0 && pending_opening(self.state, self.settings) == 0 {
+                self.inner.write_all(&self.held)?;
+                self.held.clear();
+            }
+        }
+        let opening = pending_opening(self.state, self.settings);
+        if opening > 0 && opening == self.held.len() {
+            // Only the opening of a comment is held, with no comma before it to look ahead from.
+            let comma = strip_chunk(&mut self.state, input, 0, self.settings, &mut self.progress)?;
+            self.restore_held_opening();
+            let opening = pending_opening(self.state, self.settings);
+            if opening > input.len() {
+                return Ok(0);
+            }
+            self.inner.write_all(&self.held)?;
+            self.held.clear();
+            return Ok(comma.unwrap_or(input.len()).min(input.len() - opening));
+        }
+        let mut start = 0;
+        if !self.held.is_empty() {
+            let mut i = 0;
+            match lookahead(
+                &mut self.state,
+                input,
+                &mut i,
+                self.settings,
+                &mut self.progress,
+            )? {
+                Lookahead::OutOfInput => {
+                    self.restore_held_opening();
+                    return Ok(0);
+                }
+                Lookahead::Trailing if self.settings.trailing_commas && self.held[0] == b',' => {
+                    self.held[0] = self.settings.fill;
+                    self.progress.trailing_comma(0);
+                }
+                Lookahead::Trailing | Lookahead::NotTrailing => {}
+            }
+            self.restore_held_opening();
+            self.inner.write_all(&self.held)?;
+            self.held.clear();
+            // A comma or bracket that came next needs its own look ahead, so strip it again.
+            start = if needs_lookahead(input[i], self.settings) {
+                i
+            } else {
+                i + 1
+            };
+        }
+        let comma = strip_chunk(
+            &mut self.state,
+            input,
+            start,
+            self.settings,
+            &mut self.progress,
+        )?;
+        let opening = pending_opening(self.state, self.settings);
+        Ok(comma.unwrap_or(input.len()).min(input.len() - opening))
+    }
+
+    /// Puts back the part of the opening of a kept comment that is held, once stripping shows
+    /// that the comment is kept.
+    fn restore_held_opening(&mut self) {
+        let before = std::mem::take(&mut self.progress.opening_before_input);
+        if before > 0 {
+            let prefix = self.settings.preserved_prefix();
+            let held = self.held.len();
+            restore_opening(&mut self.held[held - before..], prefix, 0);
+        }
+    }
+}
+
+impl<W: Write> Write for StripCommentsWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut input = std::mem::take(&mut self.partial_start);
+        input.extend_from_slice(buf);
+        if self.progress.consumed == 0 && partial_start(&input, self.settings) {
+            self.partial_start = input;
+            return Ok(buf.len());
+        }
+        self.strip_and_write(input)?;
+        Ok(buf.len())
+    }
+
+    /// Flushes the underlying writer. A comma held back to see whether it is trailing stays
+    /// held until more is written or the writer is finished.
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ErrorKind, Write};
+
+    use super::StripCommentsWriter;
+    use crate::{strip_str, CommentPrefix, CommentSettings, StripError, StripErrorKind};
+
+    fn write_chunked(
+        settings: CommentSettings,
+        input: &str,
+        chunk: usize,
+    ) -> Result<Vec<u8>, std::io::Error> {
+        let mut writer = StripCommentsWriter::with_settings(settings, Vec::new());
+        for part in input.as_bytes().chunks(chunk) {
+            writer.write_all(part)?;
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn matches_strip_in_any_chunks() {
+        let input =
+            "{\"a\": [1, 2, ], // x, ]\n \"b\": \"/* ,] */\", /* c */ \"d\": [,], \"e\": 1,\n}";
+        let expected = strip_str(input).unwrap();
+        for chunk in 1..=input.len() {
+            let out = write_chunked(CommentSettings::all(), input, chunk).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                expected,
+                "chunks of {chunk}"
+            );
+        }
+
+        // A comma at the very end is not trailing.
+        let out = write_chunked(CommentSettings::all(), "[1] ,", 1).unwrap();
+        assert_eq!(out, b"[1] ,");
+    }
+
+    #[test]
+    fn preserve_comment_prefix() {
+        let settings = CommentSettings {
+            preserve_comment_prefix: CommentPrefix::new(b"*!"),
+            ..CommentSettings::all()
+        };
+        let input = "/**! (c) */ [1, /**/ 2, /*!*/ 3, /**!*/] /**! [ */ // x";
+        let expected = "/**! (c) */ [1,      2,       3  /**!*/] /**! [ */     ";
+        for chunk in 1..=input.len() {
+            let out = write_chunked(settings, input, chunk).unwrap();
+            assert_eq!(
+                String::from_utf8(out).unwrap(),
+                expected,
+                "chunks of {chunk}"
+            );
+        }
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let settings = CommentSettings {
+            skip_bom: true,
+            ..CommentSettings::all()
+        };
+        for chunk in [1, 2, 3, 8] {
+            let out = write_chunked(settings, "\u{feff}[1] // x", chunk).unwrap();
+            assert_eq!(out, b"   [1]     ");
+        }
+        let out = write_chunked(settings, "\u{feff}", 1).unwrap();
+        assert_eq!(out, b"   ");
+        // Only part of a mark is passed through.
+        let mut writer = StripCommentsWriter::with_settings(settings, Vec::new());
+        writer.write_all(&[0xEF, 0xBB]).unwrap();
+        assert_eq!(writer.finish().unwrap(), [0xEF, 0xBB]);
+    }
+
+    #[test]
+    fn leading_shebang() {
+        let settings = CommentSettings {
+            strip_leading_shebang: true,
+            ..CommentSettings::c_style()
+        };
+        for chunk in [1, 2, 8] {
+            let out = write_chunked(settings, "#!/bin/x\n[1] #!", chunk).unwrap();
+            assert_eq!(out, b"        \n[1] #!");
+        }
+        assert_eq!(write_chunked(settings, "#", 1).unwrap(), b"#");
+    }
+
+    #[test]
+    fn empty_elements() {
+        let settings = CommentSettings {
+            elide_empty_elements: true,
+            ..CommentSettings::all()
+        };
+        for chunk in [1, 2, 3, 8] {
+            let out = write_chunked(settings, "{\"a\": [,1,, [,]],,}", chunk).unwrap();
+            assert_eq!(out, b"{\"a\": [ 1,  [ ]]  }");
+            let out = write_chunked(CommentSettings::all(), "[1,,]", chunk).unwrap();
+            assert_eq!(out, b"[1, ]");
+        }
+    }
+
+    #[test]
+    fn lone_slashes() {
+        let settings = CommentSettings {
+            lone_slashes: true,
+            ..CommentSettings::all()
+        };
+        for chunk in [1, 2, 3, 8] {
+            let out = write_chunked(settings, "{a/b: [1, /, //c\n/*d*/], e/: /}", chunk).unwrap();
+            assert_eq!(out, b"{a/b: [1, /     \n     ], e/: /}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let err = write_chunked(CommentSettings::all(), "[1] /* open", 2).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err = err.get_ref().unwrap().downcast_ref::<StripError>().unwrap();
+        assert_eq!(err.kind(), StripErrorKind::UnterminatedBlockComment);
+        assert_eq!(err.offset(), 4);
+
+        let mut writer = StripCommentsWriter::new(Vec::new());
+        writer.write_all(b"[1, 2").unwrap();
+        let err = writer.write_all(b", /x]").unwrap_err();
+        let err = err.get_ref().unwrap().downcast_ref::<StripError>().unwrap();
+        assert_eq!(err.offset(), 8);
+    }
+}
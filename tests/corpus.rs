@@ -0,0 +1,95 @@
+//! Regression corpus for inputs found by fuzzing or that exercise tricky corners.
+//!
+//! Each `tests/corpus/<name>.input` has either a `<name>.output` holding the expected stripped
+//! bytes, or an empty `<name>.error` marking input that the reader must reject with
+//! [`ErrorKind::InvalidData`]. Every input is run through the reader, both in one read and a
+//! few bytes at a time, and through [`strip_comments_in_place`] when it is UTF-8, once with each
+//! [`ScanStrategy`].
+
+use std::{
+    fs,
+    io::{ErrorKind, Read, Result},
+    path::{Path, PathBuf},
+};
+
+use json_strip_comments::{strip_comments_in_place, CommentSettings, ScanStrategy};
+
+/// Reads the whole input in a single `read` call.
+fn read_stripped(input: &[u8], settings: CommentSettings) -> Result<Vec<u8>> {
+    let mut out = vec![0; input.len()];
+    let mut reader = settings.strip_comments(input);
+    let count = reader.read(&mut out)?;
+    assert_eq!(count, input.len());
+    assert_eq!(reader.read(&mut [0; 1])?, 0);
+    Ok(out)
+}
+
+/// Reads the input with the inner reader handing out at most `chunk` bytes at a time.
+fn read_stripped_chunked(input: &[u8], settings: CommentSettings, chunk: usize) -> Result<Vec<u8>> {
+    struct Chunked<'a>(&'a [u8], usize);
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = self.1.min(buf.len()).min(self.0.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let mut out = Vec::new();
+    settings
+        .strip_comments(Chunked(input, chunk))
+        .read_to_end(&mut out)?;
+    Ok(out)
+}
+
+fn inputs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut inputs: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "input"))
+        .collect();
+    inputs.sort();
+    inputs
+}
+
+#[test]
+fn corpus() {
+    let inputs = inputs();
+    assert!(!inputs.is_empty());
+    for path in inputs {
+        let input = fs::read(&path).unwrap();
+        let expected = fs::read(path.with_extension("output"));
+        let expect_error = path.with_extension("error").exists();
+        assert!(
+            expected.is_ok() != expect_error,
+            "{} needs exactly one of .output or .error",
+            path.display()
+        );
+
+        for scan_strategy in [ScanStrategy::Branch, ScanStrategy::Table] {
+            let mut settings = CommentSettings::default();
+            settings.scan_strategy = scan_strategy;
+            let name = format!("{} with {scan_strategy:?}", path.display());
+
+            let chunked = [1, 3].map(|chunk| read_stripped_chunked(&input, settings, chunk));
+            for result in [read_stripped(&input, settings)].into_iter().chain(chunked) {
+                match &expected {
+                    Ok(expected) => assert_eq!(&result.unwrap(), expected, "{name}"),
+                    Err(_) => {
+                        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData, "{name}")
+                    }
+                }
+            }
+
+            // The in-place functions don't check for unterminated comments and strings at the
+            // end, so only successful output is compared.
+            if let (Ok(mut s), Ok(expected)) = (String::from_utf8(input.clone()), &expected) {
+                strip_comments_in_place(&mut s, settings).unwrap();
+                assert_eq!(s.as_bytes(), expected, "{name} in place");
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Guards against accidental quadratic behavior on inputs that alternate rapidly between
+//! strings, comments, and commas.
+//!
+//! A linear pass over these inputs takes milliseconds, while a quadratic one would take
+//! minutes, so the time budget is generous enough not to be flaky on slow machines.
+
+use std::time::{Duration, Instant};
+
+use json_strip_comments::{
+    restore_placeholders, sindresorhus_compat, strip, strip_chunks, strip_compact,
+    strip_with_placeholders,
+};
+
+const REPEAT: usize = 50_000;
+const BUDGET: Duration = Duration::from_secs(10);
+
+/// `""/**/"" /**/ ` repeated, which switches state every couple of bytes.
+fn alternating() -> String {
+    r#"""/**/"" /**/ "#.repeat(REPEAT)
+}
+
+/// An array of strings each followed by a comma and a comment, so every comma starts a
+/// trailing-comma lookahead.
+fn alternating_commas() -> String {
+    format!("[{}\"\"]", r#""",/**/"#.repeat(REPEAT))
+}
+
+fn timed<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+    assert!(elapsed < BUDGET, "{name} took {elapsed:?}");
+    result
+}
+
+#[test]
+fn strip_is_linear() {
+    for input in [alternating(), alternating_commas()] {
+        let mut stripped = input.clone();
+        timed("strip", || strip(&mut stripped)).unwrap();
+        assert_eq!(stripped.len(), input.len());
+        assert!(!stripped.contains('/'));
+    }
+}
+
+#[test]
+fn comment_apis_are_linear() {
+    let input = alternating();
+    let comments_len = "/**/".len() * 2 * REPEAT;
+
+    let compact = timed("strip_compact", || strip_compact(&input)).unwrap();
+    assert_eq!(compact.len(), input.len() - comments_len);
+
+    let chunks = timed("strip_chunks", || {
+        strip_chunks(&input).map(Result::unwrap).count()
+    });
+    // Two kept and two removed chunks per repeat, plus the final space.
+    assert_eq!(chunks, 4 * REPEAT + 1);
+
+    let (skeleton, comments) = timed("strip_with_placeholders", || {
+        strip_with_placeholders(&input)
+    })
+    .unwrap();
+    assert_eq!(comments.len(), 2 * REPEAT);
+    let restored = timed("restore_placeholders", || {
+        restore_placeholders(&skeleton, &comments)
+    });
+    assert_eq!(restored.unwrap(), input);
+
+    let options = sindresorhus_compat::Options {
+        whitespace: false,
+        trailing_commas: true,
+    };
+    let compat = timed("sindresorhus_compat::strip", || {
+        sindresorhus_compat::strip(&alternating_commas(), options)
+    });
+    assert_eq!(
+        compat.len(),
+        alternating_commas().len() - "/**/".len() * REPEAT
+    );
+}
@@ -1,5 +1,4 @@
-// use json_strip_comments::{strip, CommentSettings};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tsify::Tsify;
 use wasm_bindgen::prelude::*;
 
@@ -32,17 +31,52 @@ pub struct CommentSettings {
     pub trailing_commas: Option<bool>,
 }
 
+/// The result of [`strip_checked`].
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct StripResult {
+    /// The stripped string. When the input is invalid, it is stripped up to the problem.
+    pub output: String,
+
+    /// False if the input is invalid, such as when it ends inside a string or block comment.
+    pub ok: bool,
+
+    /// What is wrong with the input, when `ok` is false.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Strips comments and trailing commas by replacing them with whitespaces.
+///
+/// Invalid input is stripped as far as it goes. Use `stripChecked` to find out whether it was.
 #[wasm_bindgen]
 pub fn strip(string: String, settings: Option<CommentSettings>) -> String {
     let mut string = string;
-    let settings = settings.unwrap_or_default();
-    let settings = json_strip_comments::CommentSettings {
-        block_comments: settings.block_comments.unwrap_or(true),
-        slash_line_comments: settings.slash_line_comments.unwrap_or(true),
-        hash_line_comments: settings.hash_line_comments.unwrap_or(true),
-        trailing_commas: settings.trailing_commas.unwrap_or(true),
-    };
-    let _ = json_strip_comments::strip_comments_in_place(&mut string, settings);
+    let _ = json_strip_comments::strip_comments_in_place(&mut string, to_settings(settings));
     string
 }
+
+/// Like `strip`, but also reports whether the input was valid. Input that ends inside a string
+/// or block comment, or has a `/` that starts no comment, is invalid.
+#[wasm_bindgen(js_name = stripChecked)]
+pub fn strip_checked(string: String, settings: Option<CommentSettings>) -> StripResult {
+    let mut output = string;
+    let result =
+        json_strip_comments::strip_comments_in_place_strict(&mut output, to_settings(settings));
+    StripResult {
+        output,
+        ok: result.is_ok(),
+        error: result.err().map(|err| err.to_string()),
+    }
+}
+
+fn to_settings(settings: Option<CommentSettings>) -> json_strip_comments::CommentSettings {
+    let settings = settings.unwrap_or_default();
+    let mut out = json_strip_comments::CommentSettings::default();
+    out.block_comments = settings.block_comments.unwrap_or(true);
+    out.slash_line_comments = settings.slash_line_comments.unwrap_or(true);
+    out.hash_line_comments = settings.hash_line_comments.unwrap_or(true);
+    out.trailing_commas = settings.trailing_commas.unwrap_or(true);
+    out
+}
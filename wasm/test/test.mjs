@@ -1,5 +1,5 @@
 import assert from 'assert';
-import { strip } from '../../npm/json_strip_comments_wasm.js';
+import { strip, stripChecked } from '../../npm/json_strip_comments_wasm.js';
 
 const s = `
 {
@@ -32,3 +32,17 @@ const expected = `
 });
 
 assert.strictEqual(strip(s, { blockComments: false, slashLineComments: false, hashLineComments: false, trailingCommas: false }), s);
+
+// Disabled comment styles pass through untouched.
+const hashKept = strip(s, { hashLineComments: false });
+assert.ok(hashKept.includes('"age": 43, # hash line comment\n'));
+assert.ok(!hashKept.includes('/* full */') && !hashKept.includes('// work phone'));
+
+// `stripChecked` reports input that `strip` passes through.
+assert.deepStrictEqual(stripChecked('[1,] // x'), { output: '[1 ]     ', ok: true });
+assert.deepStrictEqual(stripChecked('[1] /* open'), {
+    output: '[1]        ',
+    ok: false,
+    error: 'unterminated block comment at offset 4',
+});
+assert.strictEqual(stripChecked('["open').ok, false);